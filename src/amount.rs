@@ -0,0 +1,467 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use crate::Error;
+
+/// An amount of bitcoin in satoshis, never above [`Amount::MAX_MONEY`].
+///
+/// The `+` and `-` operators panic when the result leaves that range, like
+/// integer overflow does in debug builds; use the `checked_` and
+/// `saturating_` methods where the inputs are not trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_SAT: Amount = Amount(1);
+    pub const ONE_BTC: Amount = Amount(100_000_000);
+    /// The 21 million BTC supply cap, Core's `MAX_MONEY`.
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * 100_000_000);
+
+    /// Fails with [`Error::AmountOutOfRange`] above [`Amount::MAX_MONEY`].
+    pub fn from_sat(sat: u64) -> Result<Amount, Error> {
+        if sat > Self::MAX_MONEY.0 {
+            return Err(Error::AmountOutOfRange { sat });
+        }
+        Ok(Amount(sat))
+    }
+
+    pub fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        Self::from_sat(self.0 + rhs.0).ok()
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Amount> {
+        Self::from_sat(self.0.checked_mul(rhs)?).ok()
+    }
+
+    pub fn checked_div(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_div(rhs).map(Amount)
+    }
+
+    pub fn saturating_add(self, rhs: Amount) -> Amount {
+        Amount((self.0 + rhs.0).min(Self::MAX_MONEY.0))
+    }
+
+    pub fn saturating_sub(self, rhs: Amount) -> Amount {
+        Amount(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Parses a decimal amount such as `"0.0015"` counted in `denomination`.
+    /// The conversion is exact: digits below one satoshi must be zero, and
+    /// commas may group the whole part in thousands.
+    pub fn from_str_in(input: &str, denomination: Denomination) -> Result<Amount, Error> {
+        // Too many digits is out of range like any other too-large amount.
+        let invalid = |source: std::num::ParseIntError| match source.kind() {
+            std::num::IntErrorKind::PosOverflow => Error::AmountOutOfRange { sat: u64::MAX },
+            _ => Error::InvalidAmount {
+                input: input.to_string(),
+                source,
+            },
+        };
+        let invalid_digit = || Error::InvalidDigit {
+            input: input.to_string(),
+        };
+        let (whole, fraction) = match input.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (input, None),
+        };
+        // Signs are not digits, though `parse` would take a `+`, and a lone
+        // point has no digits at all.
+        if whole.starts_with(['+', '-']) || (whole.is_empty() && fraction == Some("")) {
+            return Err(invalid_digit());
+        }
+        let whole: u64 = match whole {
+            "" if fraction.is_some() => 0,
+            // Badly grouped commas are left in place for `parse` to reject.
+            _ if is_thousands_grouped(whole) => whole.replace(',', "").parse().map_err(invalid)?,
+            _ => whole.parse().map_err(invalid)?,
+        };
+
+        let decimals = denomination.decimals();
+        let mut sat = u128::from(whole) * 10u128.pow(decimals);
+        if let Some(fraction) = fraction {
+            if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid_digit());
+            }
+            let (significant, rest) = fraction.split_at(fraction.len().min(decimals as usize));
+            if rest.bytes().any(|b| b != b'0') {
+                return Err(Error::TooPrecise {
+                    input: input.to_string(),
+                    denomination,
+                });
+            }
+            if !significant.is_empty() {
+                let scale = 10u128.pow(decimals - significant.len() as u32);
+                sat += significant.parse::<u128>().unwrap() * scale;
+            }
+        }
+        Amount::from_sat(u64::try_from(sat).unwrap_or(u64::MAX))
+    }
+
+    /// Renders the amount in `denomination` with its unit, dropping
+    /// trailing fractional zeros: `"0.0015 BTC"`, `"2500 sat"`.
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        let scale = 10u64.pow(denomination.decimals());
+        let (whole, fraction) = (self.0 / scale, self.0 % scale);
+        if fraction == 0 {
+            return format!("{whole} {denomination}");
+        }
+        let digits = format!(
+            "{fraction:0width$}",
+            width = denomination.decimals() as usize
+        );
+        format!("{whole}.{} {denomination}", digits.trim_end_matches('0'))
+    }
+
+    /// Sums `amounts`, or `None` if the total exceeds [`Amount::MAX_MONEY`].
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, Amount::checked_add)
+    }
+
+    pub fn to_signed(self) -> SignedAmount {
+        SignedAmount(self.0 as i64)
+    }
+
+    /// `self - rhs`, negative when `rhs` is larger. Never fails since both
+    /// sides are within [`Amount::MAX_MONEY`].
+    pub fn signed_sub(self, rhs: Amount) -> SignedAmount {
+        SignedAmount(self.0 as i64 - rhs.0 as i64)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sat", self.0)
+    }
+}
+
+/// Parses an amount with an optional unit suffix, defaulting to satoshis:
+/// `"0.0015 BTC"`, `"150 mBTC"`, `"2,500 sat"`, `"1.5 bits"` or `"1000"`.
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        // With no number in front, the whole input is reported as a bad
+        // number rather than a bad unit.
+        let (number, unit) = match input.find(char::is_alphabetic) {
+            Some(0) | None => (input, ""),
+            Some(split) => input.split_at(split),
+        };
+        let denomination = match unit {
+            "" => Denomination::Satoshi,
+            unit => unit.parse()?,
+        };
+        Amount::from_str_in(number.trim_end(), denomination).map_err(|e| match e {
+            Error::InvalidAmount { source, .. } => Error::InvalidAmount {
+                input: input.to_string(),
+                source,
+            },
+            Error::InvalidDigit { .. } => Error::InvalidDigit {
+                input: input.to_string(),
+            },
+            Error::TooPrecise { denomination, .. } => Error::TooPrecise {
+                input: input.to_string(),
+                denomination,
+            },
+            e => e,
+        })
+    }
+}
+
+impl TryFrom<u64> for Amount {
+    type Error = Error;
+
+    fn try_from(sat: u64) -> Result<Self, Self::Error> {
+        Amount::from_sat(sat)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.0
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Amount) -> Amount {
+        self.checked_add(rhs)
+            .expect("amount addition exceeds MAX_MONEY")
+    }
+}
+
+impl AddAssign for Amount {
+    fn add_assign(&mut self, rhs: Amount) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, rhs: Amount) -> Amount {
+        self.checked_sub(rhs)
+            .expect("amount subtraction underflows")
+    }
+}
+
+impl SubAssign for Amount {
+    fn sub_assign(&mut self, rhs: Amount) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Amount {
+        iter.fold(Amount::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Amount> for Amount {
+    fn sum<I: Iterator<Item = &'a Amount>>(iter: I) -> Amount {
+        iter.copied().sum()
+    }
+}
+
+/// A balance change in satoshis, within `-MAX_MONEY..=MAX_MONEY`.
+///
+/// Operators panic outside that range, as with [`Amount`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SignedAmount(i64);
+
+impl SignedAmount {
+    pub const ZERO: SignedAmount = SignedAmount(0);
+    pub const MAX_MONEY: SignedAmount = SignedAmount(Amount::MAX_MONEY.0 as i64);
+    pub const MIN_MONEY: SignedAmount = SignedAmount(-(Amount::MAX_MONEY.0 as i64));
+
+    /// Fails with [`Error::AmountOutOfRange`] beyond [`Amount::MAX_MONEY`]
+    /// in either direction.
+    pub fn from_sat(sat: i64) -> Result<SignedAmount, Error> {
+        if sat.unsigned_abs() > Amount::MAX_MONEY.0 {
+            return Err(Error::AmountOutOfRange {
+                sat: sat.unsigned_abs(),
+            });
+        }
+        Ok(SignedAmount(sat))
+    }
+
+    pub fn to_sat(self) -> i64 {
+        self.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn unsigned_abs(self) -> Amount {
+        Amount(self.0.unsigned_abs())
+    }
+
+    /// The unsigned amount, failing with [`Error::NegativeAmount`] below zero.
+    pub fn to_unsigned(self) -> Result<Amount, Error> {
+        if self.is_negative() {
+            return Err(Error::NegativeAmount { sat: self.0 });
+        }
+        Ok(Amount(self.0 as u64))
+    }
+
+    pub fn checked_add(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        Self::from_sat(self.0 + rhs.0).ok()
+    }
+
+    pub fn checked_sub(self, rhs: SignedAmount) -> Option<SignedAmount> {
+        Self::from_sat(self.0 - rhs.0).ok()
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<SignedAmount> {
+        Self::from_sat(self.0.checked_mul(rhs)?).ok()
+    }
+
+    pub fn checked_div(self, rhs: i64) -> Option<SignedAmount> {
+        self.0.checked_div(rhs).map(SignedAmount)
+    }
+
+    /// Renders the amount like [`Amount::to_string_in`], with a leading `-`
+    /// for negative values and `+` for positive ones.
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        let sign = match self.0 {
+            ..0 => "-",
+            0 => "",
+            _ => "+",
+        };
+        format!("{sign}{}", self.unsigned_abs().to_string_in(denomination))
+    }
+}
+
+impl fmt::Display for SignedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_in(Denomination::Satoshi))
+    }
+}
+
+/// Parses an optional `+` or `-` sign followed by anything
+/// [`Amount::from_str`] accepts.
+impl FromStr for SignedAmount {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        match input.strip_prefix('-') {
+            Some(rest) => Ok(-rest.parse::<Amount>()?.to_signed()),
+            None => Ok(input
+                .strip_prefix('+')
+                .unwrap_or(input)
+                .parse::<Amount>()?
+                .to_signed()),
+        }
+    }
+}
+
+impl From<Amount> for SignedAmount {
+    fn from(amount: Amount) -> SignedAmount {
+        amount.to_signed()
+    }
+}
+
+impl TryFrom<SignedAmount> for Amount {
+    type Error = Error;
+
+    fn try_from(amount: SignedAmount) -> Result<Self, Self::Error> {
+        amount.to_unsigned()
+    }
+}
+
+impl TryFrom<i64> for SignedAmount {
+    type Error = Error;
+
+    fn try_from(sat: i64) -> Result<Self, Self::Error> {
+        SignedAmount::from_sat(sat)
+    }
+}
+
+impl Neg for SignedAmount {
+    type Output = SignedAmount;
+
+    fn neg(self) -> SignedAmount {
+        SignedAmount(-self.0)
+    }
+}
+
+impl Add for SignedAmount {
+    type Output = SignedAmount;
+
+    fn add(self, rhs: SignedAmount) -> SignedAmount {
+        self.checked_add(rhs)
+            .expect("signed amount addition exceeds MAX_MONEY")
+    }
+}
+
+impl AddAssign for SignedAmount {
+    fn add_assign(&mut self, rhs: SignedAmount) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for SignedAmount {
+    type Output = SignedAmount;
+
+    fn sub(self, rhs: SignedAmount) -> SignedAmount {
+        self.checked_sub(rhs)
+            .expect("signed amount subtraction exceeds MAX_MONEY")
+    }
+}
+
+impl SubAssign for SignedAmount {
+    fn sub_assign(&mut self, rhs: SignedAmount) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sum for SignedAmount {
+    fn sum<I: Iterator<Item = SignedAmount>>(iter: I) -> SignedAmount {
+        iter.fold(SignedAmount::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a SignedAmount> for SignedAmount {
+    fn sum<I: Iterator<Item = &'a SignedAmount>>(iter: I) -> SignedAmount {
+        iter.copied().sum()
+    }
+}
+
+/// Units an [`Amount`] can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Denomination {
+    Bitcoin,
+    MilliBitcoin,
+    /// One millionth of a bitcoin, also called a bit.
+    MicroBitcoin,
+    Satoshi,
+}
+
+impl Denomination {
+    /// Digits after the decimal point needed to express one satoshi.
+    pub fn decimals(self) -> u32 {
+        match self {
+            Denomination::Bitcoin => 8,
+            Denomination::MilliBitcoin => 5,
+            Denomination::MicroBitcoin => 2,
+            Denomination::Satoshi => 0,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Denomination::Bitcoin => "BTC",
+            Denomination::MilliBitcoin => "mBTC",
+            Denomination::MicroBitcoin => "bits",
+            Denomination::Satoshi => "sat",
+        })
+    }
+}
+
+/// Accepts the common spellings; the SI prefix is case-sensitive so that
+/// `"MBTC"` is not mistaken for millibitcoin.
+impl FromStr for Denomination {
+    type Err = Error;
+
+    fn from_str(unit: &str) -> Result<Self, Self::Err> {
+        match unit {
+            "BTC" | "btc" => Ok(Denomination::Bitcoin),
+            "mBTC" | "mbtc" => Ok(Denomination::MilliBitcoin),
+            "uBTC" | "ubtc" | "µBTC" | "µbtc" | "bit" | "bits" => Ok(Denomination::MicroBitcoin),
+            "sat" | "sats" | "satoshi" | "satoshis" => Ok(Denomination::Satoshi),
+            _ => Err(Error::UnknownDenomination {
+                unit: unit.to_string(),
+            }),
+        }
+    }
+}
+
+/// `1,234,567`-style grouping: a lead group of one to three digits, then
+/// groups of exactly three.
+fn is_thousands_grouped(whole: &str) -> bool {
+    let mut groups = whole.split(',');
+    let lead = groups.next().unwrap_or_default();
+    whole.contains(',') && (1..=3).contains(&lead.len()) && groups.all(|group| group.len() == 3)
+}
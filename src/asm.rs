@@ -0,0 +1,131 @@
+use crate::script::{
+    decode_script_num, encode_script_num, is_valid_signature_encoding, push_data, sighash_name,
+};
+use crate::{Error, Instruction, Opcode, bytes_to_hex, decode_hex, instructions};
+
+/// Renders a script in Bitcoin Core's ASM format.
+///
+/// Pushes of up to four bytes print as numbers, larger ones as hex. A push
+/// holding a strict-DER signature with a defined sighash type prints as
+/// `<hex>[ALL]`, like Core does for scriptSigs. Undefined opcodes print as
+/// `OP_UNKNOWN` and a truncated push ends the output with `[error]`.
+pub fn disassemble(script: &[u8]) -> String {
+    let unspendable = script.first() == Some(&Opcode::OpReturn.to_byte());
+    let mut parts = Vec::new();
+    for ins in instructions(script) {
+        match ins {
+            Ok(Instruction::Push(data)) if data.len() <= 4 => {
+                // At most four bytes, which always decode.
+                parts.push(decode_script_num(data, false, 4).unwrap().to_string())
+            }
+            Ok(Instruction::Push(data)) => match data.split_last() {
+                Some((&sighash, sig)) if !unspendable && is_valid_signature_encoding(data) => {
+                    match sighash_name(sighash) {
+                        Some(name) => parts.push(format!("{}[{name}]", bytes_to_hex(sig))),
+                        None => parts.push(bytes_to_hex(data)),
+                    }
+                }
+                _ => parts.push(bytes_to_hex(data)),
+            },
+            Ok(Instruction::Op(op)) => parts.push(op_name(op)),
+            Err(Error::InvalidOpcode { .. }) => parts.push("OP_UNKNOWN".to_string()),
+            Err(_) => {
+                parts.push("[error]".to_string());
+                break;
+            }
+        }
+    }
+    parts.join(" ")
+}
+
+/// Core's `GetOpName`, which prints small integers as plain numbers.
+fn op_name(op: Opcode) -> String {
+    match op {
+        Opcode::Op1negate => "-1".to_string(),
+        _ if (0x51..=0x60).contains(&op.to_byte()) => (op.to_byte() - 0x50).to_string(),
+        _ => op.to_string(),
+    }
+}
+
+/// Parses ASM text into script bytes, accepting the output of
+/// [`disassemble`] plus the conventions of Core's test fixtures:
+///
+/// - decimal numbers in `±0xffffffff`, pushed as minimal `CScriptNum`s
+///   (`0`, `-1` and `1..=16` become their opcodes)
+/// - opcode names with or without the `OP_` prefix
+/// - hex data, pushed with the shortest length prefix, optionally followed
+///   by a sighash suffix such as `[ALL]`
+/// - `0x`-prefixed hex, inserted raw without a push opcode
+/// - `'text'`, pushed as its UTF-8 bytes
+///
+/// Numbers are tried before hex, as in Core's `ParseScript`, so the ASM
+/// format cannot tell a push whose hex is all digits from a number. Such
+/// pushes do not survive a round trip through [`disassemble`]: `0102030405`
+/// reads back as the number 102030405, and digits beyond the number range
+/// are rejected.
+pub fn assemble(asm: &str) -> Result<Vec<u8>, Error> {
+    let mut script = Vec::new();
+    for token in asm.split_whitespace() {
+        let offset = token.as_ptr() as usize - asm.as_ptr() as usize;
+        let invalid = || Error::InvalidAsmToken {
+            token: token.to_string(),
+            offset,
+        };
+
+        if let Some(value) = parse_number(token) {
+            let value = value.ok_or_else(invalid)?;
+            match value {
+                0 => script.push(Opcode::Op0.to_byte()),
+                -1 => script.push(Opcode::Op1negate.to_byte()),
+                1..=16 => script.push(0x50 + value as u8),
+                _ => push_data(&mut script, &encode_script_num(value)),
+            }
+        } else if let Some(raw) = token.strip_prefix("0x") {
+            if raw.is_empty() {
+                return Err(invalid());
+            }
+            script.extend(decode_hex(raw).map_err(|_| invalid())?);
+        } else if let Some(text) = token.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+            push_data(&mut script, text.as_bytes());
+        } else if let Some(op) = parse_opcode(token) {
+            script.push(op.to_byte());
+        } else {
+            let (hex, sighash) = match token.split_once('[') {
+                Some((hex, suffix)) => {
+                    let name = suffix.strip_suffix(']').ok_or_else(invalid)?;
+                    let byte = (0..=u8::MAX)
+                        .find(|b| sighash_name(*b) == Some(name))
+                        .ok_or_else(invalid)?;
+                    (hex, Some(byte))
+                }
+                None => (token, None),
+            };
+            let mut data = decode_hex(hex).map_err(|_| invalid())?;
+            data.extend(sighash);
+            push_data(&mut script, &data);
+        }
+    }
+    Ok(script)
+}
+
+/// `None` if the token is not a decimal number, `Some(None)` if it is one
+/// but lies outside the range Core accepts.
+fn parse_number(token: &str) -> Option<Option<i64>> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(
+        token
+            .parse::<i64>()
+            .ok()
+            .filter(|n| n.unsigned_abs() <= 0xffff_ffff),
+    )
+}
+
+fn parse_opcode(token: &str) -> Option<Opcode> {
+    token
+        .parse()
+        .or_else(|_| format!("OP_{token}").parse())
+        .ok()
+}
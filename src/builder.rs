@@ -0,0 +1,112 @@
+use crate::script::{encode_script_num, is_pubkey, push_data};
+use crate::{Error, Opcode};
+
+/// Fluent builder for script bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptBuilder {
+    script: Vec<u8>,
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_opcode(mut self, op: Opcode) -> Self {
+        self.script.push(op.to_byte());
+        self
+    }
+
+    /// Pushes `data` using the encoding the MINIMALDATA rule demands: the
+    /// small-number opcodes where they apply, else the shortest push.
+    pub fn push_slice(mut self, data: &[u8]) -> Self {
+        match data {
+            [] => self.script.push(Opcode::Op0.to_byte()),
+            [n @ 1..=16] => self.script.push(0x50 + n),
+            [0x81] => self.script.push(Opcode::Op1negate.to_byte()),
+            _ => push_data(&mut self.script, data),
+        }
+        self
+    }
+
+    pub fn push_int(self, value: i64) -> Self {
+        self.push_slice(&encode_script_num(value))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.script
+    }
+
+    pub fn into_script(self) -> Vec<u8> {
+        self.script
+    }
+
+    pub fn p2pkh(pubkey_hash: &[u8; 20]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_opcode(Opcode::OpDup)
+            .push_opcode(Opcode::OpHash160)
+            .push_slice(pubkey_hash)
+            .push_opcode(Opcode::OpEqualverify)
+            .push_opcode(Opcode::OpChecksig)
+            .into_script()
+    }
+
+    pub fn p2sh(script_hash: &[u8; 20]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_opcode(Opcode::OpHash160)
+            .push_slice(script_hash)
+            .push_opcode(Opcode::OpEqual)
+            .into_script()
+    }
+
+    pub fn p2wpkh(pubkey_hash: &[u8; 20]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_int(0)
+            .push_slice(pubkey_hash)
+            .into_script()
+    }
+
+    pub fn p2wsh(script_hash: &[u8; 32]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_int(0)
+            .push_slice(script_hash)
+            .into_script()
+    }
+
+    pub fn p2tr(output_key: &[u8; 32]) -> Vec<u8> {
+        ScriptBuilder::new()
+            .push_int(1)
+            .push_slice(output_key)
+            .into_script()
+    }
+
+    /// Bare `m`-of-`n` multisig. Fails unless `1 <= m <= n <= 16` and every
+    /// key is a well-formed 33 or 65 byte public key.
+    pub fn multisig(required: u8, pubkeys: &[&[u8]]) -> Result<Vec<u8>, Error> {
+        let count = pubkeys.len();
+        if required == 0 || required as usize > count || count > 16 {
+            return Err(Error::InvalidMultisig { required, count });
+        }
+        if let Some(index) = pubkeys.iter().position(|key| !is_pubkey(key)) {
+            return Err(Error::InvalidPubkey { index });
+        }
+        let builder = pubkeys
+            .iter()
+            .fold(ScriptBuilder::new().push_int(required as i64), |b, key| {
+                b.push_slice(key)
+            });
+        Ok(builder
+            .push_int(count as i64)
+            .push_opcode(Opcode::OpCheckmultisig)
+            .into_script())
+    }
+
+    /// `OP_RETURN <data>`, or a bare `OP_RETURN` when `data` is empty.
+    pub fn null_data(data: &[u8]) -> Vec<u8> {
+        let mut script = vec![Opcode::OpReturn.to_byte()];
+        if !data.is_empty() {
+            push_data(&mut script, data);
+        }
+        script
+    }
+}
@@ -0,0 +1,213 @@
+use k256::PublicKey;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+use crate::script::decode_script_pubkey;
+use crate::{Amount, Error, MAX_SCRIPT_SIZE, Outpoint, ScriptBuilder, ScriptPubKey, UTXO};
+
+/// Script size codes below this select one of the templates.
+const SPECIAL_SCRIPTS: u64 = 6;
+
+/// Core's `CompressAmount`: strips trailing decimal zeros into an exponent
+/// so round amounts encode in few bytes. Like Core it wraps on inputs far
+/// beyond the coin supply.
+pub fn compress_amount(sat: u64) -> u64 {
+    if sat == 0 {
+        return 0;
+    }
+    let (mut n, mut e) = (sat, 0);
+    while n % 10 == 0 && e < 9 {
+        n /= 10;
+        e += 1;
+    }
+    if e < 9 {
+        let d = n % 10;
+        n /= 10;
+        (n.wrapping_mul(9) + d - 1)
+            .wrapping_mul(10)
+            .wrapping_add(1 + e)
+    } else {
+        (n - 1).wrapping_mul(10).wrapping_add(10)
+    }
+}
+
+/// Core's `DecompressAmount`, the inverse of [`compress_amount`]. Like
+/// Core it wraps on inputs no amount compresses to.
+pub fn decompress_amount(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    let x = x - 1;
+    let (e, x) = (x % 10, x / 10);
+    let mut n = if e < 9 {
+        let d = x % 9 + 1;
+        (x / 9).wrapping_mul(10).wrapping_add(d)
+    } else {
+        x + 1
+    };
+    for _ in 0..e {
+        n = n.wrapping_mul(10);
+    }
+    n
+}
+
+/// Core's `CompressScript`: the one-byte template code and payload for a
+/// P2PKH, P2SH or P2PK script, or `None` for any other script. Uncompressed
+/// keys must be valid points, as their y coordinate is dropped.
+pub fn compress_script(script_pubkey: &[u8]) -> Option<Vec<u8>> {
+    let (code, payload): (u8, &[u8]) = match decode_script_pubkey(script_pubkey) {
+        ScriptPubKey::P2PKH { pubkey_hash } => (0x00, pubkey_hash),
+        ScriptPubKey::P2SH { script_hash } => (0x01, script_hash),
+        ScriptPubKey::P2PK { pubkey } => match pubkey[0] {
+            0x02 | 0x03 => (pubkey[0], &pubkey[1..]),
+            0x04 if PublicKey::from_sec1_bytes(pubkey).is_ok() => {
+                (0x04 | (pubkey[64] & 1), &pubkey[1..33])
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut out = vec![code];
+    out.extend_from_slice(payload);
+    Some(out)
+}
+
+/// Rebuilds the script [`compress_script`] encoded as `code` and `payload`.
+/// `None` if the code is not a template, the payload has the wrong length
+/// or an x coordinate is not on the curve.
+pub fn decompress_script(code: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    if payload.len() != special_script_size(code.into())? {
+        return None;
+    }
+    let script = match code {
+        0x00 => ScriptBuilder::p2pkh(payload.try_into().unwrap()),
+        0x01 => ScriptBuilder::p2sh(payload.try_into().unwrap()),
+        0x02 | 0x03 => p2pk([&[code], payload].concat()),
+        _ => {
+            let compressed = [&[code - 2], payload].concat();
+            let key = PublicKey::from_sec1_bytes(&compressed).ok()?;
+            p2pk(key.to_encoded_point(false).as_bytes().to_vec())
+        }
+    };
+    Some(script)
+}
+
+/// Serializes a coin the way Core's `Coin` is stored: a VARINT of
+/// `height * 2 + is_coinbase`, the compressed amount as a VARINT, then the
+/// script as a template or as a VARINT of its length plus six and the raw
+/// bytes. The outpoint is not included; Core keys coins by it.
+pub fn compress_coin(utxo: &UTXO) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(
+        &mut out,
+        u64::from(utxo.height) * 2 + u64::from(utxo.is_coinbase),
+    );
+    write_varint(&mut out, compress_amount(utxo.value.to_sat()));
+    match compress_script(&utxo.script_pubkey) {
+        Some(compressed) => out.extend(compressed),
+        None => {
+            write_varint(&mut out, utxo.script_pubkey.len() as u64 + SPECIAL_SCRIPTS);
+            out.extend_from_slice(&utxo.script_pubkey);
+        }
+    }
+    out
+}
+
+/// Parses a coin written by [`compress_coin`], which must span all of
+/// `bytes`, as the coin at `outpoint`.
+pub fn decompress_coin(outpoint: Outpoint, bytes: &[u8]) -> Result<UTXO, Error> {
+    match read_coin(outpoint, bytes)? {
+        (utxo, len) if len == bytes.len() => Ok(utxo),
+        _ => Err(Error::InvalidCoin),
+    }
+}
+
+/// Parses a coin from the front of `bytes`, returning it with the number of
+/// bytes it took.
+pub(crate) fn read_coin(outpoint: Outpoint, bytes: &[u8]) -> Result<(UTXO, usize), Error> {
+    let mut pos = 0;
+    let code = read_varint(bytes, &mut pos)?;
+    let height = u32::try_from(code >> 1).map_err(|_| Error::InvalidCoin)?;
+    let value = Amount::from_sat(decompress_amount(read_varint(bytes, &mut pos)?))
+        .map_err(|_| Error::InvalidCoin)?;
+
+    let size = read_varint(bytes, &mut pos)?;
+    let script_pubkey = match special_script_size(size) {
+        Some(len) => {
+            let payload = take(bytes, &mut pos, len)?;
+            decompress_script(size as u8, payload).ok_or(Error::InvalidCoin)?
+        }
+        None => {
+            let len = usize::try_from(size - SPECIAL_SCRIPTS).map_err(|_| Error::InvalidCoin)?;
+            let script = take(bytes, &mut pos, len)?;
+            // Core stores oversized scripts as a bare OP_RETURN, since they
+            // can never be spent.
+            if len > MAX_SCRIPT_SIZE {
+                vec![0x6a]
+            } else {
+                script.to_vec()
+            }
+        }
+    };
+    let utxo = UTXO {
+        txid: outpoint.0,
+        vout: outpoint.1,
+        value,
+        script_pubkey,
+        height,
+        is_coinbase: code & 1 == 1,
+    };
+    Ok((utxo, pos))
+}
+
+fn special_script_size(code: u64) -> Option<usize> {
+    match code {
+        0x00 | 0x01 => Some(20),
+        0x02..=0x05 => Some(32),
+        _ => None,
+    }
+}
+
+fn p2pk(pubkey: Vec<u8>) -> Vec<u8> {
+    let mut script = vec![pubkey.len() as u8];
+    script.extend(pubkey);
+    script.push(0xac);
+    script
+}
+
+/// Core's VARINT: big-endian base-128 where each continuation subtracts
+/// one, so every value has exactly one encoding.
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    let mut tmp = Vec::with_capacity(10);
+    loop {
+        let continuation = if tmp.is_empty() { 0x00 } else { 0x80 };
+        tmp.push((n & 0x7f) as u8 | continuation);
+        if n <= 0x7f {
+            break;
+        }
+        n = (n >> 7) - 1;
+    }
+    out.extend(tmp.iter().rev());
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut n: u64 = 0;
+    loop {
+        let byte = *bytes.get(*pos).ok_or(Error::InvalidCoin)?;
+        *pos += 1;
+        if n > u64::MAX >> 7 {
+            return Err(Error::InvalidCoin);
+        }
+        n = (n << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        n = n.checked_add(1).ok_or(Error::InvalidCoin)?;
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], Error> {
+    let end = pos.checked_add(len).ok_or(Error::InvalidCoin)?;
+    let slice = bytes.get(*pos..end).ok_or(Error::InvalidCoin)?;
+    *pos = end;
+    Ok(slice)
+}
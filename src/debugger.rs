@@ -0,0 +1,311 @@
+use std::fmt;
+
+use crate::interpreter::{Vm, verify_with};
+use crate::{
+    Error, MAX_SCRIPT_SIZE, Opcode, ScriptError, SignatureChecker, VerifyFlags, bytes_to_hex,
+    disassemble,
+};
+
+/// One executed instruction, with the machine state around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Byte offset of the instruction within its script.
+    pub offset: usize,
+    /// `None` for bytes that are not defined opcodes.
+    pub opcode: Option<Opcode>,
+    /// The instruction in ASM form, including any pushed data.
+    pub asm: String,
+    /// Whether the instruction sat in an executing branch. Unexecuted
+    /// instructions are still traced since flow control and limits apply.
+    pub executed: bool,
+    pub stack_before: Vec<Vec<u8>>,
+    pub stack_after: Vec<Vec<u8>>,
+    pub altstack_after: Vec<Vec<u8>>,
+    /// The IF/NOTIF condition stack after the instruction, innermost last.
+    pub branch: Vec<bool>,
+    /// Set on the instruction that made the script fail.
+    pub error: Option<ScriptError>,
+}
+
+/// The recorded run of a single script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub script: Vec<u8>,
+    pub steps: Vec<TraceStep>,
+    pub result: Result<(), ScriptError>,
+}
+
+impl Trace {
+    /// The step that failed, if the failure is tied to an instruction.
+    /// Script-level failures such as an oversized script or an unclosed IF
+    /// have none.
+    pub fn failed_step(&self) -> Option<&TraceStep> {
+        self.steps.last().filter(|step| step.error.is_some())
+    }
+
+    /// A plain-text table with one row per step.
+    pub fn to_table(&self) -> String {
+        let rows: Vec<[String; 6]> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                [
+                    i.to_string(),
+                    step.offset.to_string(),
+                    step.asm.clone(),
+                    branch_label(step),
+                    stack_label(&step.stack_before),
+                    match step.error {
+                        Some(e) => format!("error: {e}"),
+                        None => stack_label(&step.stack_after),
+                    },
+                ]
+            })
+            .collect();
+        let header = [
+            "#",
+            "offset",
+            "instruction",
+            "branch",
+            "stack before",
+            "stack after",
+        ];
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let mut out = String::new();
+        let mut line = |cells: &[&str]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            out.push_str(padded.join("  ").trim_end());
+            out.push('\n');
+        };
+        line(&header);
+        for row in &rows {
+            line(&row.each_ref().map(String::as_str));
+        }
+        match self.result {
+            Ok(()) => out.push_str("result: ok\n"),
+            Err(e) => out.push_str(&format!("result: {e}\n")),
+        }
+        out
+    }
+
+    /// The trace as a JSON object. Stack elements are hex strings, top last.
+    pub fn to_json(&self) -> String {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{{\"offset\":{},\"opcode\":{},\"asm\":{},\"executed\":{},\
+                     \"stack_before\":{},\"stack_after\":{},\"altstack_after\":{},\
+                     \"branch\":[{}],\"error\":{}}}",
+                    step.offset,
+                    step.opcode
+                        .map_or("null".to_string(), |op| json_string(&op.to_string())),
+                    json_string(&step.asm),
+                    step.executed,
+                    json_stack(&step.stack_before),
+                    json_stack(&step.stack_after),
+                    json_stack(&step.altstack_after),
+                    step.branch
+                        .iter()
+                        .map(bool::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    step.error
+                        .map_or("null".to_string(), |e| json_string(&e.to_string())),
+                )
+            })
+            .collect();
+        format!(
+            "{{\"script\":{},\"steps\":[{}],\"error\":{}}}",
+            json_string(&bytes_to_hex(&self.script)),
+            steps.join(","),
+            self.result
+                .err()
+                .map_or("null".to_string(), |e| json_string(&e.to_string())),
+        )
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_table())
+    }
+}
+
+/// Steps through a script one instruction at a time, recording a
+/// [`TraceStep`] for each.
+pub struct Debugger<'a> {
+    vm: Vm<'a>,
+    steps: Vec<TraceStep>,
+    result: Option<Result<(), ScriptError>>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(
+        stack: Vec<Vec<u8>>,
+        script: &'a [u8],
+        flags: VerifyFlags,
+        checker: &'a dyn SignatureChecker,
+    ) -> Self {
+        let result = (script.len() > MAX_SCRIPT_SIZE).then_some(Err(ScriptError::ScriptSize));
+        Debugger {
+            vm: Vm::new(stack, script, flags, checker),
+            steps: Vec::new(),
+            result,
+        }
+    }
+
+    /// Executes the next instruction and returns its record, or `None` once
+    /// the script has finished or failed.
+    pub fn step(&mut self) -> Option<&TraceStep> {
+        if self.result.is_some() {
+            return None;
+        }
+        let offset = self.vm.pos;
+        let stack_before = self.vm.stack.clone();
+        let executed = self.vm.executing();
+        let error = match self.vm.step() {
+            Ok(false) => {
+                self.result = Some(self.vm.finish());
+                return None;
+            }
+            Ok(true) => None,
+            Err(e) => {
+                self.result = Some(Err(e));
+                Some(e)
+            }
+        };
+
+        let script = self.vm.script;
+        self.steps.push(TraceStep {
+            offset,
+            opcode: Opcode::from_byte(script[offset]).ok(),
+            asm: disassemble(&script[offset..self.vm.pos]),
+            executed,
+            stack_before,
+            stack_after: self.vm.stack.clone(),
+            altstack_after: self.vm.altstack.clone(),
+            branch: self.vm.exec.clone(),
+            error,
+        });
+        self.steps.last()
+    }
+
+    pub fn stack(&self) -> &[Vec<u8>] {
+        &self.vm.stack
+    }
+
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// The outcome, once [`step`](Self::step) has returned `None`.
+    pub fn result(&self) -> Option<Result<(), ScriptError>> {
+        self.result
+    }
+
+    /// Runs the remaining instructions and returns the full trace along with
+    /// the final stack.
+    pub fn finish(mut self) -> (Trace, Vec<Vec<u8>>) {
+        while self.step().is_some() {}
+        let trace = Trace {
+            script: self.vm.script.to_vec(),
+            steps: self.steps,
+            // `step` only stops once a result is recorded.
+            result: self.result.unwrap(),
+        };
+        (trace, self.vm.stack)
+    }
+}
+
+/// Traced form of [`eval_script`](crate::eval_script).
+pub fn trace_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    flags: VerifyFlags,
+    checker: &dyn SignatureChecker,
+) -> Trace {
+    let (trace, final_stack) =
+        Debugger::new(std::mem::take(stack), script, flags, checker).finish();
+    *stack = final_stack;
+    trace
+}
+
+/// Traced form of [`verify_script`](crate::verify_script). Returns the
+/// verification result and one trace per script run: the scriptSig, the
+/// scriptPubKey, then the redeem script for P2SH spends. Evaluation stops at
+/// the first failing script, so its trace is the last one.
+pub fn trace_verify_script(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    flags: VerifyFlags,
+    checker: &dyn SignatureChecker,
+) -> (Result<(), Error>, Vec<Trace>) {
+    let mut traces = Vec::new();
+    let result = verify_with(script_sig, script_pubkey, flags, |stack, script| {
+        let trace = trace_script(stack, script, flags, checker);
+        let result = trace.result.map_err(Error::Script);
+        traces.push(trace);
+        result
+    });
+    (result, traces)
+}
+
+fn branch_label(step: &TraceStep) -> String {
+    let state: String = step
+        .branch
+        .iter()
+        .map(|taken| if *taken { 'T' } else { 'F' })
+        .collect();
+    match (step.executed, state.is_empty()) {
+        (true, true) => "-".to_string(),
+        (true, false) => state,
+        (false, _) => format!("{state} (skip)"),
+    }
+}
+
+fn stack_label(stack: &[Vec<u8>]) -> String {
+    let items: Vec<String> = stack
+        .iter()
+        .map(|item| match item.is_empty() {
+            true => "''".to_string(),
+            false => bytes_to_hex(item),
+        })
+        .collect();
+    format!("[{}]", items.join(" "))
+}
+
+fn json_stack(stack: &[Vec<u8>]) -> String {
+    let items: Vec<String> = stack
+        .iter()
+        .map(|item| json_string(&bytes_to_hex(item)))
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
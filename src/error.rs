@@ -0,0 +1,245 @@
+use std::fmt;
+use std::num::ParseIntError;
+
+use crate::{Amount, Denomination, Outpoint};
+
+/// Every failure the crate can report.
+#[derive(Debug)]
+pub enum Error {
+    /// A character outside `[0-9a-fA-F]` at the given byte offset.
+    InvalidHexCharacter { character: char, offset: usize },
+    /// Hex input must encode whole bytes.
+    OddHexLength { length: usize },
+    /// A hash must be exactly 32 bytes long.
+    InvalidHashLength { length: usize },
+    /// Outpoints are written `txid:vout`.
+    InvalidOutpoint { input: String },
+    /// A serialized outpoint is exactly 36 bytes.
+    InvalidOutpointLength { length: usize },
+    /// The input is not a valid amount.
+    InvalidAmount {
+        input: String,
+        source: ParseIntError,
+    },
+    /// The amount has a sign or other character where a digit belongs, or
+    /// no digits at all.
+    InvalidDigit { input: String },
+    /// The amount has digits below one satoshi.
+    TooPrecise {
+        input: String,
+        denomination: Denomination,
+    },
+    /// The unit suffix of an amount is not a known denomination.
+    UnknownDenomination { unit: String },
+    /// The satoshi value is above [`Amount::MAX_MONEY`].
+    AmountOutOfRange { sat: u64 },
+    /// A negative amount where only non-negative ones are valid.
+    NegativeAmount { sat: i64 },
+    /// Spending `required` from a balance of only `available`.
+    InsufficientFunds { available: Amount, required: Amount },
+    /// No coin exists at the outpoint.
+    MissingUtxo { outpoint: Outpoint },
+    /// A coin already exists at the outpoint.
+    DuplicateUtxo { outpoint: Outpoint },
+    /// Undo data does not match the block it is meant to revert.
+    UndoMismatch,
+    /// Reading or writing a UTXO store failed.
+    Io(std::io::Error),
+    /// Bytes that do not decode as a compressed coin.
+    InvalidCoin,
+    /// A write to a UTXO store failed and could not be undone on disk; it
+    /// must be reopened.
+    StorePoisoned,
+    /// A UTXO store's files are damaged beyond a torn final write.
+    CorruptStore,
+    /// The byte does not map to a known opcode.
+    InvalidOpcode { byte: u8 },
+    /// The text is not the name of any opcode.
+    InvalidOpcodeName { name: String },
+    /// The push starting at `offset` needs `expected` more bytes than the
+    /// `available` ones left in the script.
+    TruncatedPush {
+        offset: usize,
+        expected: usize,
+        available: usize,
+    },
+    /// An ASM token that is neither a number, an opcode nor hex data.
+    InvalidAsmToken { token: String, offset: usize },
+    /// Multisig needs `1 <= required <= count <= 16`.
+    InvalidMultisig { required: u8, count: usize },
+    /// The key at `index` is not a valid serialized public key.
+    InvalidPubkey { index: usize },
+    /// Script execution failed.
+    Script(ScriptError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidHexCharacter { character, offset } => {
+                write!(f, "invalid hex character {character:?} at offset {offset}")
+            }
+            Error::OddHexLength { length } => {
+                write!(f, "hex string has odd length {length}")
+            }
+            Error::InvalidHashLength { length } => {
+                write!(f, "hash must be 32 bytes, got {length}")
+            }
+            Error::InvalidOutpoint { input } => {
+                write!(f, "invalid outpoint {input:?}, expected txid:vout")
+            }
+            Error::InvalidOutpointLength { length } => {
+                write!(f, "serialized outpoint must be 36 bytes, got {length}")
+            }
+            Error::InvalidAmount { input, .. } => {
+                write!(f, "invalid satoshi amount {input:?}")
+            }
+            Error::InvalidDigit { input } => write!(f, "invalid digit in amount {input:?}"),
+            Error::TooPrecise {
+                input,
+                denomination,
+            } => write!(
+                f,
+                "amount {input:?} is more precise than one satoshi in {denomination}"
+            ),
+            Error::UnknownDenomination { unit } => write!(f, "unknown denomination {unit:?}"),
+            Error::AmountOutOfRange { sat } => {
+                write!(f, "amount of {sat} sat exceeds the 21 million BTC supply")
+            }
+            Error::NegativeAmount { sat } => write!(f, "amount of {sat} sat is negative"),
+            Error::InsufficientFunds {
+                available,
+                required,
+            } => write!(f, "insufficient funds: need {required}, have {available}"),
+            Error::MissingUtxo { outpoint } => write!(f, "no unspent output at {outpoint}"),
+            Error::DuplicateUtxo { outpoint } => {
+                write!(f, "output {outpoint} already exists")
+            }
+            Error::UndoMismatch => write!(f, "undo data does not match the block"),
+            Error::InvalidCoin => write!(f, "malformed compressed coin"),
+            Error::Io(_) => write!(f, "UTXO store I/O failed"),
+            Error::StorePoisoned => write!(f, "UTXO store needs reopening after a failed write"),
+            Error::CorruptStore => write!(f, "UTXO store is corrupt"),
+            Error::InvalidOpcode { byte } => write!(f, "invalid opcode 0x{byte:02x}"),
+            Error::InvalidOpcodeName { name } => write!(f, "unknown opcode name {name:?}"),
+            Error::TruncatedPush {
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "push at offset {offset} needs {expected} bytes but only {available} remain"
+            ),
+            Error::InvalidAsmToken { token, offset } => {
+                write!(f, "invalid ASM token {token:?} at offset {offset}")
+            }
+            Error::InvalidMultisig { required, count } => {
+                write!(f, "invalid {required}-of-{count} multisig")
+            }
+            Error::InvalidPubkey { index } => write!(f, "invalid public key at index {index}"),
+            Error::Script(_) => write!(f, "script verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidAmount { source, .. } => Some(source),
+            Error::Script(source) => Some(source),
+            Error::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Why a script failed to execute, mirroring Bitcoin Core's `ScriptError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptError {
+    EvalFalse,
+    OpReturn,
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    SigCount,
+    PubkeyCount,
+    Verify,
+    EqualVerify,
+    CheckMultisigVerify,
+    CheckSigVerify,
+    NumEqualVerify,
+    BadOpcode,
+    DisabledOpcode,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    UnbalancedConditional,
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+    SigHashtype,
+    SigDer,
+    MinimalData,
+    SigPushOnly,
+    SigHighS,
+    SigNullDummy,
+    PubkeyType,
+    CleanStack,
+    SigNullFail,
+    DiscourageUpgradableNops,
+    /// A numeric operand was too long or not minimally encoded; Core
+    /// reports this as `UNKNOWN_ERROR`.
+    InvalidNumber,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            ScriptError::EvalFalse => {
+                "script evaluated without error but finished with a false/empty top stack element"
+            }
+            ScriptError::OpReturn => "OP_RETURN was encountered",
+            ScriptError::ScriptSize => "script is too big",
+            ScriptError::PushSize => "push value size limit exceeded",
+            ScriptError::OpCount => "operation limit exceeded",
+            ScriptError::StackSize => "stack size limit exceeded",
+            ScriptError::SigCount => "signature count negative or greater than pubkey count",
+            ScriptError::PubkeyCount => "pubkey count negative or limit exceeded",
+            ScriptError::Verify => "script failed an OP_VERIFY operation",
+            ScriptError::EqualVerify => "script failed an OP_EQUALVERIFY operation",
+            ScriptError::CheckMultisigVerify => "script failed an OP_CHECKMULTISIGVERIFY operation",
+            ScriptError::CheckSigVerify => "script failed an OP_CHECKSIGVERIFY operation",
+            ScriptError::NumEqualVerify => "script failed an OP_NUMEQUALVERIFY operation",
+            ScriptError::BadOpcode => "opcode missing or not understood",
+            ScriptError::DisabledOpcode => "attempted to use a disabled opcode",
+            ScriptError::InvalidStackOperation => "operation not valid with the current stack size",
+            ScriptError::InvalidAltstackOperation => {
+                "operation not valid with the current altstack size"
+            }
+            ScriptError::UnbalancedConditional => "invalid OP_IF construction",
+            ScriptError::NegativeLocktime => "negative locktime",
+            ScriptError::UnsatisfiedLocktime => "locktime requirement not satisfied",
+            ScriptError::SigHashtype => "signature hash type missing or not understood",
+            ScriptError::SigDer => "non-canonical DER signature",
+            ScriptError::MinimalData => "data push larger than necessary",
+            ScriptError::SigPushOnly => "only push operators allowed in signatures",
+            ScriptError::SigHighS => "non-canonical signature: S value is unnecessarily high",
+            ScriptError::SigNullDummy => "dummy CHECKMULTISIG argument must be zero",
+            ScriptError::PubkeyType => "public key is neither compressed or uncompressed",
+            ScriptError::CleanStack => "stack size must be exactly one after execution",
+            ScriptError::SigNullFail => {
+                "signature must be zero for failed CHECK(MULTI)SIG operation"
+            }
+            ScriptError::DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            ScriptError::InvalidNumber => "script number overflow or non-minimal encoding",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for ScriptError {}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::Amount;
+
+/// Witness bytes count once towards weight, all other bytes four times.
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// Transaction weight in weight units, as defined by BIP141.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Weight(u64);
+
+impl Weight {
+    pub const ZERO: Weight = Weight(0);
+    /// Consensus limit on the weight of a block.
+    pub const MAX_BLOCK: Weight = Weight(4_000_000);
+
+    pub fn from_wu(wu: u64) -> Weight {
+        Weight(wu)
+    }
+
+    /// The weight of `vbytes` non-witness bytes.
+    pub fn from_vbytes(vbytes: u64) -> Weight {
+        Weight(vbytes * WITNESS_SCALE_FACTOR)
+    }
+
+    /// Weight from the serialized sizes of a transaction: `stripped_size`
+    /// without witness data and `total_size` with it.
+    pub fn from_tx_sizes(stripped_size: u64, total_size: u64) -> Weight {
+        Weight(stripped_size * (WITNESS_SCALE_FACTOR - 1) + total_size)
+    }
+
+    /// Weight of `non_witness` bytes plus `witness` bytes.
+    pub fn from_parts(non_witness: u64, witness: u64) -> Weight {
+        Weight(non_witness * WITNESS_SCALE_FACTOR + witness)
+    }
+
+    pub fn to_wu(self) -> u64 {
+        self.0
+    }
+
+    /// Virtual size, rounded up like Core's `GetVirtualTransactionSize`.
+    pub fn to_vbytes_ceil(self) -> u64 {
+        self.0.div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    pub fn checked_add(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_add(rhs.0).map(Weight)
+    }
+}
+
+impl fmt::Display for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wu", self.0)
+    }
+}
+
+/// A fee rate, held in satoshis per 1000 weight units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    pub const ZERO: FeeRate = FeeRate(0);
+    /// Core's default `-minrelaytxfee` of 1 sat/vB.
+    pub const MIN_RELAY: FeeRate = FeeRate(250);
+    /// Core's default `-dustrelayfee` of 3 sat/vB.
+    pub const DUST_RELAY: FeeRate = FeeRate(750);
+
+    pub fn from_sat_per_kwu(sat_per_kwu: u64) -> FeeRate {
+        FeeRate(sat_per_kwu)
+    }
+
+    /// `None` if the rate does not fit in sat/kWU.
+    pub fn from_sat_per_vb(sat_per_vb: u64) -> Option<FeeRate> {
+        sat_per_vb
+            .checked_mul(1000 / WITNESS_SCALE_FACTOR)
+            .map(FeeRate)
+    }
+
+    /// Core's native unit, sat per 1000 virtual bytes. Rounded up to the
+    /// next sat/kWU, so fees at the result never fall short of the rate.
+    pub fn from_sat_per_kvb(sat_per_kvb: u64) -> FeeRate {
+        FeeRate(sat_per_kvb.div_ceil(WITNESS_SCALE_FACTOR))
+    }
+
+    pub fn to_sat_per_kwu(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_sat_per_vb_floor(self) -> u64 {
+        self.0 / (1000 / WITNESS_SCALE_FACTOR)
+    }
+
+    pub fn to_sat_per_vb_ceil(self) -> u64 {
+        self.0.div_ceil(1000 / WITNESS_SCALE_FACTOR)
+    }
+
+    /// The fee for a transaction of `weight` at this rate, rounded up to the
+    /// next satoshi. `None` if it would exceed [`Amount::MAX_MONEY`].
+    pub fn fee_for(self, weight: Weight) -> Option<Amount> {
+        let fee = (u128::from(self.0) * u128::from(weight.0)).div_ceil(1000);
+        Amount::from_sat(u64::try_from(fee).ok()?).ok()
+    }
+
+    /// The fee for `vbytes` virtual bytes, as Core computes it from a
+    /// transaction's virtual size.
+    pub fn fee_for_vbytes(self, vbytes: u64) -> Option<Amount> {
+        self.fee_for(Weight::from_vbytes(vbytes))
+    }
+
+    /// The rate that `fee` pays for `weight`, rounded down. `None` for a
+    /// zero weight.
+    pub fn rate_of(fee: Amount, weight: Weight) -> Option<FeeRate> {
+        // At most MAX_MONEY * 1000, which fits in a u64.
+        (fee.to_sat() * 1000).checked_div(weight.0).map(FeeRate)
+    }
+}
+
+/// Renders sat/vB with up to three decimals, which is exact for a rate in
+/// sat/kWU.
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_vb = 1000 / WITNESS_SCALE_FACTOR;
+        let (whole, fraction) = (self.0 / per_vb, self.0 % per_vb);
+        if fraction == 0 {
+            return write!(f, "{whole} sat/vB");
+        }
+        let digits = format!("{:03}", fraction * WITNESS_SCALE_FACTOR);
+        write!(f, "{whole}.{} sat/vB", digits.trim_end_matches('0'))
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ripemd::Ripemd160;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{Error, bytes_to_hex, decode_hex, to_big_endian};
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// SHA256 applied twice, as used for txids and block hashes.
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    sha256(&sha256(data))
+}
+
+/// RIPEMD160 of SHA256, as used for P2PKH and P2SH.
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    ripemd160(&sha256(data))
+}
+
+pub(crate) fn ripemd160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(data).into()
+}
+
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    Sha1::digest(data).into()
+}
+
+macro_rules! hash_types {
+    ($($(#[$doc:meta])* $name:ident;)*) => {$(
+        $(#[$doc])*
+        ///
+        /// Stored in internal byte order; parsed and displayed as hex in
+        /// Bitcoin's reversed order.
+        #[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name([u8; 32]);
+
+        impl $name {
+            pub const fn all_zeros() -> Self {
+                $name([0; 32])
+            }
+
+            pub const fn from_byte_array(bytes: [u8; 32]) -> Self {
+                $name(bytes)
+            }
+
+            /// Fails with [`Error::InvalidHashLength`] unless `bytes` holds
+            /// exactly 32 bytes.
+            pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+                bytes
+                    .try_into()
+                    .map($name)
+                    .map_err(|_| Error::InvalidHashLength {
+                        length: bytes.len(),
+                    })
+            }
+
+            /// The double-SHA256 of `data`.
+            pub fn hash(data: &[u8]) -> Self {
+                $name(sha256d(data))
+            }
+
+            pub fn as_byte_array(&self) -> &[u8; 32] {
+                &self.0
+            }
+
+            pub fn to_byte_array(self) -> [u8; 32] {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&bytes_to_hex(&to_big_endian(&self.0)))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({self})", stringify!($name))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(hex: &str) -> Result<Self, Self::Err> {
+                Self::from_slice(&to_big_endian(&decode_hex(hex)?))
+            }
+        }
+    )*};
+}
+
+hash_types! {
+    /// A transaction id: the double-SHA256 of a transaction without its
+    /// witness data.
+    Txid;
+    /// A witness transaction id, committing to witness data as well.
+    Wtxid;
+    /// The double-SHA256 of a block header.
+    BlockHash;
+    /// A commitment to a UTXO set, as `gettxoutsetinfo` reports it.
+    UtxoSetHash;
+}
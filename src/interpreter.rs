@@ -0,0 +1,731 @@
+use std::ops::BitOr;
+
+use crate::hash::{hash160, ripemd160, sha1, sha256, sha256d};
+use crate::script::{
+    decode_script_num, encode_script_num, is_push_only, is_valid_signature_encoding, push_data,
+};
+use crate::{Error, Instruction, Opcode, ScriptError, ScriptType, classify_script, instructions};
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_STACK_SIZE: usize = 1_000;
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+
+/// Script verification flags, named after Bitcoin Core's `SCRIPT_VERIFY_*`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    pub const NONE: VerifyFlags = VerifyFlags(0);
+    pub const P2SH: VerifyFlags = VerifyFlags(1 << 0);
+    pub const STRICTENC: VerifyFlags = VerifyFlags(1 << 1);
+    pub const DERSIG: VerifyFlags = VerifyFlags(1 << 2);
+    pub const LOW_S: VerifyFlags = VerifyFlags(1 << 3);
+    pub const NULLDUMMY: VerifyFlags = VerifyFlags(1 << 4);
+    pub const SIGPUSHONLY: VerifyFlags = VerifyFlags(1 << 5);
+    pub const MINIMALDATA: VerifyFlags = VerifyFlags(1 << 6);
+    pub const DISCOURAGE_UPGRADABLE_NOPS: VerifyFlags = VerifyFlags(1 << 7);
+    pub const CLEANSTACK: VerifyFlags = VerifyFlags(1 << 8);
+    pub const CHECKLOCKTIMEVERIFY: VerifyFlags = VerifyFlags(1 << 9);
+    pub const CHECKSEQUENCEVERIFY: VerifyFlags = VerifyFlags(1 << 10);
+    pub const NULLFAIL: VerifyFlags = VerifyFlags(1 << 14);
+
+    pub fn contains(self, other: VerifyFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for VerifyFlags {
+    type Output = VerifyFlags;
+
+    fn bitor(self, rhs: VerifyFlags) -> VerifyFlags {
+        VerifyFlags(self.0 | rhs.0)
+    }
+}
+
+/// Transaction-dependent checks the interpreter delegates to its caller.
+///
+/// Only `check_sig` is required; the locktime checks default to failing,
+/// like Core's `BaseSignatureChecker`.
+pub trait SignatureChecker {
+    /// `sig` still carries its sighash byte. `script_code` is the script
+    /// from the last `OP_CODESEPARATOR` with the signature removed.
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool;
+
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+/// Checker for scripts evaluated outside a transaction: every signature and
+/// locktime check fails.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoSignatureChecker;
+
+impl SignatureChecker for NoSignatureChecker {
+    fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+        false
+    }
+}
+
+/// Executes `script` on `stack`, like Core's `EvalScript` for legacy
+/// (pre-segwit) scripts.
+pub fn eval_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    flags: VerifyFlags,
+    checker: &dyn SignatureChecker,
+) -> Result<(), Error> {
+    let mut vm = Vm::new(std::mem::take(stack), script, flags, checker);
+    let result = vm.run();
+    *stack = vm.stack;
+    result.map_err(Error::Script)
+}
+
+/// Runs `script_sig` then `script_pubkey`, and the redeem script when P2SH
+/// is enabled, like Core's `VerifyScript` without witness support.
+pub fn verify_script(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    flags: VerifyFlags,
+    checker: &dyn SignatureChecker,
+) -> Result<(), Error> {
+    verify_with(script_sig, script_pubkey, flags, |stack, script| {
+        eval_script(stack, script, flags, checker)
+    })
+}
+
+/// The `VerifyScript` driver, with script evaluation left to `eval` so the
+/// debugger can record each script it runs.
+pub(crate) fn verify_with(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    flags: VerifyFlags,
+    mut eval: impl FnMut(&mut Vec<Vec<u8>>, &[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let fail = |e| Err(Error::Script(e));
+    if flags.contains(VerifyFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+        return fail(ScriptError::SigPushOnly);
+    }
+
+    let mut stack = Vec::new();
+    eval(&mut stack, script_sig)?;
+    let stack_copy = flags.contains(VerifyFlags::P2SH).then(|| stack.clone());
+    eval(&mut stack, script_pubkey)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return fail(ScriptError::EvalFalse);
+    }
+
+    if let Some(mut stack_copy) =
+        stack_copy.filter(|_| classify_script(script_pubkey) == ScriptType::P2SH)
+    {
+        if !is_push_only(script_sig) {
+            return fail(ScriptError::SigPushOnly);
+        }
+        // The scriptSig left at least one element for the scriptPubKey to
+        // hash, so the copy cannot be empty here.
+        let redeem_script = stack_copy.pop().unwrap();
+        eval(&mut stack_copy, &redeem_script)?;
+        if !stack_copy.last().is_some_and(|top| cast_to_bool(top)) {
+            return fail(ScriptError::EvalFalse);
+        }
+        stack = stack_copy;
+    }
+
+    if flags.contains(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+        return fail(ScriptError::CleanStack);
+    }
+    Ok(())
+}
+
+/// Stack element truthiness: anything but zero or negative zero.
+pub fn cast_to_bool(data: &[u8]) -> bool {
+    match data.split_last() {
+        Some((&last, rest)) => rest.iter().any(|b| *b != 0) || (last != 0 && last != 0x80),
+        None => false,
+    }
+}
+
+pub(crate) struct Vm<'a> {
+    pub(crate) script: &'a [u8],
+    pub(crate) pos: usize,
+    flags: VerifyFlags,
+    checker: &'a dyn SignatureChecker,
+    pub(crate) stack: Vec<Vec<u8>>,
+    pub(crate) altstack: Vec<Vec<u8>>,
+    pub(crate) exec: Vec<bool>,
+    op_count: usize,
+    code_separator: usize,
+}
+
+impl<'a> Vm<'a> {
+    pub(crate) fn new(
+        stack: Vec<Vec<u8>>,
+        script: &'a [u8],
+        flags: VerifyFlags,
+        checker: &'a dyn SignatureChecker,
+    ) -> Self {
+        Vm {
+            script,
+            pos: 0,
+            flags,
+            checker,
+            stack,
+            altstack: Vec::new(),
+            exec: Vec::new(),
+            op_count: 0,
+            code_separator: 0,
+        }
+    }
+
+    fn run(&mut self) -> Result<(), ScriptError> {
+        if self.script.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::ScriptSize);
+        }
+        while self.step()? {}
+        self.finish()
+    }
+
+    pub(crate) fn executing(&self) -> bool {
+        !self.exec.contains(&false)
+    }
+
+    pub(crate) fn finish(&self) -> Result<(), ScriptError> {
+        if self.exec.is_empty() {
+            Ok(())
+        } else {
+            Err(ScriptError::UnbalancedConditional)
+        }
+    }
+
+    /// Executes one instruction; `Ok(false)` once the script is exhausted.
+    pub(crate) fn step(&mut self) -> Result<bool, ScriptError> {
+        if self.pos >= self.script.len() {
+            return Ok(false);
+        }
+        let executing = self.executing();
+        let offset = self.pos;
+        let mut iter = instructions(&self.script[offset..]);
+        let ins = iter.next().unwrap();
+        self.pos += iter.position();
+
+        match ins {
+            Ok(Instruction::Push(data)) => {
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(ScriptError::PushSize);
+                }
+                if executing {
+                    if self.flags.contains(VerifyFlags::MINIMALDATA)
+                        && !is_minimal_push(self.script[offset], data)
+                    {
+                        return Err(ScriptError::MinimalData);
+                    }
+                    self.stack.push(data.to_vec());
+                }
+            }
+            Ok(Instruction::Op(op)) => {
+                self.count_op(op.to_byte())?;
+                if op.is_disabled() {
+                    return Err(ScriptError::DisabledOpcode);
+                }
+                // Flow control runs even in unexecuted branches.
+                if executing || (0x63..=0x68).contains(&op.to_byte()) {
+                    self.execute(op, executing)?;
+                }
+            }
+            Err(Error::InvalidOpcode { byte }) => {
+                self.count_op(byte)?;
+                if executing {
+                    return Err(ScriptError::BadOpcode);
+                }
+            }
+            Err(_) => return Err(ScriptError::BadOpcode),
+        }
+
+        if self.stack.len() + self.altstack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+        Ok(true)
+    }
+
+    fn count_op(&mut self, byte: u8) -> Result<(), ScriptError> {
+        if byte > Opcode::Op16.to_byte() {
+            self.op_count += 1;
+            if self.op_count > MAX_OPS_PER_SCRIPT {
+                return Err(ScriptError::OpCount);
+            }
+        }
+        Ok(())
+    }
+
+    fn require(&self, n: usize) -> Result<(), ScriptError> {
+        if self.stack.len() < n {
+            return Err(ScriptError::InvalidStackOperation);
+        }
+        Ok(())
+    }
+
+    /// The element `depth` places from the top, 1 being the top itself.
+    fn top(&self, depth: usize) -> &Vec<u8> {
+        &self.stack[self.stack.len() - depth]
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::InvalidStackOperation)
+    }
+
+    fn num(&self, depth: usize, max_size: usize) -> Result<i64, ScriptError> {
+        let minimal = self.flags.contains(VerifyFlags::MINIMALDATA);
+        decode_script_num(self.top(depth), minimal, max_size)
+    }
+
+    fn push_num(&mut self, value: i64) {
+        self.stack.push(encode_script_num(value));
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.stack.push(if value { vec![1] } else { vec![] });
+    }
+
+    fn execute(&mut self, op: Opcode, executing: bool) -> Result<(), ScriptError> {
+        use Opcode::*;
+
+        match op {
+            Op1negate => self.push_num(-1),
+            Op1 | Op2 | Op3 | Op4 | Op5 | Op6 | Op7 | Op8 | Op9 | Op10 | Op11 | Op12 | Op13
+            | Op14 | Op15 | Op16 => self.push_num((op.to_byte() - 0x50) as i64),
+
+            OpNop => {}
+            OpChecklocktimeverify if self.flags.contains(VerifyFlags::CHECKLOCKTIMEVERIFY) => {
+                self.require(1)?;
+                let lock_time = self.num(1, 5)?;
+                if lock_time < 0 {
+                    return Err(ScriptError::NegativeLocktime);
+                }
+                if !self.checker.check_lock_time(lock_time) {
+                    return Err(ScriptError::UnsatisfiedLocktime);
+                }
+            }
+            OpChecksequenceverify if self.flags.contains(VerifyFlags::CHECKSEQUENCEVERIFY) => {
+                self.require(1)?;
+                let sequence = self.num(1, 5)?;
+                if sequence < 0 {
+                    return Err(ScriptError::NegativeLocktime);
+                }
+                if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0
+                    && !self.checker.check_sequence(sequence)
+                {
+                    return Err(ScriptError::UnsatisfiedLocktime);
+                }
+            }
+            OpNop1
+            | OpChecklocktimeverify
+            | OpChecksequenceverify
+            | OpNop4
+            | OpNop5
+            | OpNop6
+            | OpNop7
+            | OpNop8
+            | OpNop9
+            | OpNop10 => {
+                if self.flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                    return Err(ScriptError::DiscourageUpgradableNops);
+                }
+            }
+
+            OpIf | OpNotif => {
+                let mut value = false;
+                if executing {
+                    let top = self.stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                    value = cast_to_bool(&top) == (op == OpIf);
+                }
+                self.exec.push(value);
+            }
+            OpElse => {
+                let top = self
+                    .exec
+                    .last_mut()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+                *top = !*top;
+            }
+            OpEndif => {
+                self.exec.pop().ok_or(ScriptError::UnbalancedConditional)?;
+            }
+            OpVerify => {
+                self.require(1)?;
+                if !cast_to_bool(self.top(1)) {
+                    return Err(ScriptError::Verify);
+                }
+                self.stack.pop();
+            }
+            OpReturn => return Err(ScriptError::OpReturn),
+
+            OpToaltstack => {
+                let top = self.pop()?;
+                self.altstack.push(top);
+            }
+            OpFromaltstack => {
+                let top = self
+                    .altstack
+                    .pop()
+                    .ok_or(ScriptError::InvalidAltstackOperation)?;
+                self.stack.push(top);
+            }
+            Op2drop => {
+                self.require(2)?;
+                self.stack.truncate(self.stack.len() - 2);
+            }
+            Op2dup => {
+                self.require(2)?;
+                let (a, b) = (self.top(2).clone(), self.top(1).clone());
+                self.stack.extend([a, b]);
+            }
+            Op3dup => {
+                self.require(3)?;
+                let items = self.stack[self.stack.len() - 3..].to_vec();
+                self.stack.extend(items);
+            }
+            Op2over => {
+                self.require(4)?;
+                let (a, b) = (self.top(4).clone(), self.top(3).clone());
+                self.stack.extend([a, b]);
+            }
+            Op2rot => {
+                self.require(6)?;
+                let at = self.stack.len() - 6;
+                let items: Vec<_> = self.stack.drain(at..at + 2).collect();
+                self.stack.extend(items);
+            }
+            Op2swap => {
+                self.require(4)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 4, len - 2);
+                self.stack.swap(len - 3, len - 1);
+            }
+            OpIfdup => {
+                self.require(1)?;
+                if cast_to_bool(self.top(1)) {
+                    self.stack.push(self.top(1).clone());
+                }
+            }
+            OpDepth => self.push_num(self.stack.len() as i64),
+            OpDrop => {
+                self.pop()?;
+            }
+            OpDup => {
+                self.require(1)?;
+                self.stack.push(self.top(1).clone());
+            }
+            OpNip => {
+                self.require(2)?;
+                self.stack.remove(self.stack.len() - 2);
+            }
+            OpOver => {
+                self.require(2)?;
+                self.stack.push(self.top(2).clone());
+            }
+            OpPick | OpRoll => {
+                self.require(2)?;
+                let n = self.num(1, 4)?;
+                self.stack.pop();
+                if n < 0 || n as usize >= self.stack.len() {
+                    return Err(ScriptError::InvalidStackOperation);
+                }
+                let index = self.stack.len() - 1 - n as usize;
+                let item = if op == OpRoll {
+                    self.stack.remove(index)
+                } else {
+                    self.stack[index].clone()
+                };
+                self.stack.push(item);
+            }
+            OpRot => {
+                self.require(3)?;
+                let at = self.stack.len() - 3;
+                let item = self.stack.remove(at);
+                self.stack.push(item);
+            }
+            OpSwap => {
+                self.require(2)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 2, len - 1);
+            }
+            OpTuck => {
+                self.require(2)?;
+                let top = self.top(1).clone();
+                self.stack.insert(self.stack.len() - 2, top);
+            }
+
+            OpSize => {
+                self.require(1)?;
+                self.push_num(self.top(1).len() as i64);
+            }
+            OpEqual | OpEqualverify => {
+                self.require(2)?;
+                let (b, a) = (self.pop()?, self.pop()?);
+                let equal = a == b;
+                self.push_bool(equal);
+                if op == OpEqualverify {
+                    if !equal {
+                        return Err(ScriptError::EqualVerify);
+                    }
+                    self.stack.pop();
+                }
+            }
+
+            Op1add | Op1sub | OpNegate | OpAbs | OpNot | Op0notequal => {
+                self.require(1)?;
+                let n = self.num(1, 4)?;
+                self.stack.pop();
+                match op {
+                    Op1add => self.push_num(n + 1),
+                    Op1sub => self.push_num(n - 1),
+                    OpNegate => self.push_num(-n),
+                    OpAbs => self.push_num(n.abs()),
+                    OpNot => self.push_num((n == 0) as i64),
+                    _ => self.push_num((n != 0) as i64),
+                }
+            }
+            OpAdd | OpSub | OpBooland | OpBoolor | OpNumequal | OpNumequalverify
+            | OpNumnotequal | OpLessthan | OpGreaterthan | OpLessthanorequal
+            | OpGreaterthanorequal | OpMin | OpMax => {
+                self.require(2)?;
+                let (a, b) = (self.num(2, 4)?, self.num(1, 4)?);
+                self.stack.truncate(self.stack.len() - 2);
+                let result = match op {
+                    OpAdd => a + b,
+                    OpSub => a - b,
+                    OpBooland => (a != 0 && b != 0) as i64,
+                    OpBoolor => (a != 0 || b != 0) as i64,
+                    OpNumequal | OpNumequalverify => (a == b) as i64,
+                    OpNumnotequal => (a != b) as i64,
+                    OpLessthan => (a < b) as i64,
+                    OpGreaterthan => (a > b) as i64,
+                    OpLessthanorequal => (a <= b) as i64,
+                    OpGreaterthanorequal => (a >= b) as i64,
+                    OpMin => a.min(b),
+                    _ => a.max(b),
+                };
+                self.push_num(result);
+                if op == OpNumequalverify {
+                    if result == 0 {
+                        return Err(ScriptError::NumEqualVerify);
+                    }
+                    self.stack.pop();
+                }
+            }
+            OpWithin => {
+                self.require(3)?;
+                let (x, min, max) = (self.num(3, 4)?, self.num(2, 4)?, self.num(1, 4)?);
+                self.stack.truncate(self.stack.len() - 3);
+                self.push_bool(min <= x && x < max);
+            }
+
+            OpRipemd160 | OpSha1 | OpSha256 | OpHash160 | OpHash256 => {
+                let data = self.pop()?;
+                let digest = match op {
+                    OpRipemd160 => ripemd160(&data).to_vec(),
+                    OpSha1 => sha1(&data).to_vec(),
+                    OpSha256 => sha256(&data).to_vec(),
+                    OpHash160 => hash160(&data).to_vec(),
+                    _ => sha256d(&data).to_vec(),
+                };
+                self.stack.push(digest);
+            }
+            OpCodeseparator => self.code_separator = self.pos,
+            OpChecksig | OpChecksigverify => {
+                self.require(2)?;
+                let (sig, pubkey) = (self.top(2).clone(), self.top(1).clone());
+                let script_code = find_and_delete(&self.script[self.code_separator..], &sig);
+                check_signature_encoding(&sig, self.flags)?;
+                check_pubkey_encoding(&pubkey, self.flags)?;
+                let success = self.checker.check_sig(&sig, &pubkey, &script_code);
+                if !success && self.flags.contains(VerifyFlags::NULLFAIL) && !sig.is_empty() {
+                    return Err(ScriptError::SigNullFail);
+                }
+                self.stack.truncate(self.stack.len() - 2);
+                self.push_bool(success);
+                if op == OpChecksigverify {
+                    if !success {
+                        return Err(ScriptError::CheckSigVerify);
+                    }
+                    self.stack.pop();
+                }
+            }
+            OpCheckmultisig | OpCheckmultisigverify => self.check_multisig(op)?,
+
+            // OP_CHECKSIGADD is tapscript only; the rest are reserved or
+            // OP_VERIF/OP_VERNOTIF, which fail even when unexecuted.
+            _ => return Err(ScriptError::BadOpcode),
+        }
+        Ok(())
+    }
+
+    fn check_multisig(&mut self, op: Opcode) -> Result<(), ScriptError> {
+        let mut i = 1;
+        self.require(i)?;
+        let mut keys_count = self.num(i, 4)?;
+        if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+            return Err(ScriptError::PubkeyCount);
+        }
+        self.op_count += keys_count as usize;
+        if self.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(ScriptError::OpCount);
+        }
+        i += 1;
+        let mut ikey = i;
+        // Number of key slots still to clean up before NULLFAIL applies.
+        let mut ikey2 = keys_count as usize + 2;
+        i += keys_count as usize;
+        self.require(i)?;
+        let mut sigs_count = self.num(i, 4)?;
+        if sigs_count < 0 || sigs_count > keys_count {
+            return Err(ScriptError::SigCount);
+        }
+        i += 1;
+        let mut isig = i;
+        i += sigs_count as usize;
+        self.require(i)?;
+
+        let mut script_code = self.script[self.code_separator..].to_vec();
+        for k in 0..sigs_count as usize {
+            script_code = find_and_delete(&script_code, self.top(isig + k));
+        }
+
+        let mut success = true;
+        while success && sigs_count > 0 {
+            let (sig, pubkey) = (self.top(isig), self.top(ikey));
+            check_signature_encoding(sig, self.flags)?;
+            check_pubkey_encoding(pubkey, self.flags)?;
+            if self.checker.check_sig(sig, pubkey, &script_code) {
+                isig += 1;
+                sigs_count -= 1;
+            }
+            ikey += 1;
+            keys_count -= 1;
+            if sigs_count > keys_count {
+                success = false;
+            }
+        }
+
+        while i > 1 {
+            i -= 1;
+            if !success
+                && self.flags.contains(VerifyFlags::NULLFAIL)
+                && ikey2 == 0
+                && !self.top(1).is_empty()
+            {
+                return Err(ScriptError::SigNullFail);
+            }
+            ikey2 = ikey2.saturating_sub(1);
+            self.stack.pop();
+        }
+
+        // Core's off-by-one: one extra element is consumed.
+        self.require(1)?;
+        if self.flags.contains(VerifyFlags::NULLDUMMY) && !self.top(1).is_empty() {
+            return Err(ScriptError::SigNullDummy);
+        }
+        self.stack.pop();
+        self.push_bool(success);
+
+        if op == Opcode::OpCheckmultisigverify {
+            if !success {
+                return Err(ScriptError::CheckMultisigVerify);
+            }
+            self.stack.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Core's `CheckMinimalPush`.
+fn is_minimal_push(opcode: u8, data: &[u8]) -> bool {
+    match data {
+        [] => opcode == Opcode::Op0.to_byte(),
+        [1..=16] | [0x81] => false,
+        _ if data.len() <= 0x4b => opcode as usize == data.len(),
+        _ if data.len() <= 0xff => opcode == Opcode::OpPushdata1.to_byte(),
+        _ if data.len() <= 0xffff => opcode == Opcode::OpPushdata2.to_byte(),
+        _ => true,
+    }
+}
+
+/// Core's `FindAndDelete`: removes every push of `sig` that starts on an
+/// instruction boundary.
+fn find_and_delete(script: &[u8], sig: &[u8]) -> Vec<u8> {
+    let mut pattern = Vec::new();
+    push_data(&mut pattern, sig);
+
+    let mut result = Vec::with_capacity(script.len());
+    let (mut pc, mut copied_to) = (0, 0);
+    loop {
+        result.extend_from_slice(&script[copied_to..pc]);
+        while script[pc..].starts_with(&pattern) {
+            pc += pattern.len();
+        }
+        copied_to = pc;
+        if pc >= script.len() {
+            break;
+        }
+        let mut iter = instructions(&script[pc..]);
+        match iter.next() {
+            Some(Err(Error::TruncatedPush { .. })) | None => break,
+            _ => pc += iter.position(),
+        }
+    }
+    result.extend_from_slice(&script[copied_to..]);
+    result
+}
+
+fn check_signature_encoding(sig: &[u8], flags: VerifyFlags) -> Result<(), ScriptError> {
+    if sig.is_empty() {
+        return Ok(());
+    }
+    let der_flags = VerifyFlags::DERSIG | VerifyFlags::LOW_S | VerifyFlags::STRICTENC;
+    if der_flags.0 & flags.0 != 0 && !is_valid_signature_encoding(sig) {
+        return Err(ScriptError::SigDer);
+    }
+    if flags.contains(VerifyFlags::LOW_S) && !is_low_s(sig) {
+        return Err(ScriptError::SigHighS);
+    }
+    if flags.contains(VerifyFlags::STRICTENC) && !matches!(sig[sig.len() - 1] & !0x80, 1..=3) {
+        return Err(ScriptError::SigHashtype);
+    }
+    Ok(())
+}
+
+fn check_pubkey_encoding(pubkey: &[u8], flags: VerifyFlags) -> Result<(), ScriptError> {
+    let valid = match pubkey.first() {
+        Some(0x02 | 0x03) => pubkey.len() == 33,
+        Some(0x04) => pubkey.len() == 65,
+        _ => false,
+    };
+    if flags.contains(VerifyFlags::STRICTENC) && !valid {
+        return Err(ScriptError::PubkeyType);
+    }
+    Ok(())
+}
+
+/// Half the secp256k1 group order; S values above it are malleable.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Expects a signature already accepted by `is_valid_signature_encoding`.
+fn is_low_s(sig: &[u8]) -> bool {
+    let len_r = sig[3] as usize;
+    let len_s = sig[5 + len_r] as usize;
+    let s = &sig[6 + len_r..6 + len_r + len_s];
+    let first = s.iter().position(|b| *b != 0).unwrap_or(s.len());
+    let s = &s[first..];
+    s.len() < 32 || (s.len() == 32 && s <= &HALF_ORDER[..])
+}
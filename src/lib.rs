@@ -1,8 +1,65 @@
-use hex::decode;
+use std::fmt;
+use std::str::FromStr;
 
-pub fn decode_hex(hex_str: &str) -> Result<Vec<u8>, String> {
+use hex::{FromHexError, decode};
+
+mod amount;
+mod asm;
+mod builder;
+mod compress;
+mod debugger;
+mod error;
+mod fee;
+mod hash;
+mod interpreter;
+mod muhash;
+mod opcode;
+mod script;
+mod size;
+mod store;
+mod transaction;
+mod utxo;
+mod wallet;
+
+pub use amount::{Amount, Denomination, SignedAmount};
+pub use asm::{assemble, disassemble};
+pub use builder::ScriptBuilder;
+pub use compress::{
+    compress_amount, compress_coin, compress_script, decompress_amount, decompress_coin,
+    decompress_script,
+};
+pub use debugger::{Debugger, Trace, TraceStep, trace_script, trace_verify_script};
+pub use error::{Error, ScriptError};
+pub use fee::{FeeRate, WITNESS_SCALE_FACTOR, Weight};
+pub use hash::{BlockHash, Txid, UtxoSetHash, Wtxid, hash160, sha256, sha256d};
+pub use interpreter::{
+    MAX_OPS_PER_SCRIPT, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE,
+    MAX_STACK_SIZE, NoSignatureChecker, SignatureChecker, VerifyFlags, cast_to_bool, eval_script,
+    verify_script,
+};
+pub use muhash::MuHash3072;
+pub use opcode::Opcode;
+pub use script::{
+    Instruction, Instructions, ScriptPubKey, ScriptType, classify_script, decode_script_num,
+    decode_script_pubkey, encode_script_num, instructions, is_unspendable,
+};
+pub use size::{InputType, dust_threshold, is_dust, output_size, output_weight};
+pub use store::{FileUtxoStore, UtxoStore};
+pub use transaction::{Transaction, TxIn, TxOut};
+pub use utxo::{BlockUndo, UtxoSet};
+pub use wallet::{Balances, TestWallet, Wallet, WalletTx};
+
+pub fn decode_hex(hex_str: &str) -> Result<Vec<u8>, Error> {
     // TODO: Decode hex string into Vec<u8>, return error string on failure
-    decode(hex_str).map_err(|e| e.to_string())
+    decode(hex_str).map_err(|e| match e {
+        FromHexError::InvalidHexCharacter { c, index } => Error::InvalidHexCharacter {
+            character: c,
+            offset: index,
+        },
+        FromHexError::OddLength | FromHexError::InvalidStringLength => Error::OddHexLength {
+            length: hex_str.len(),
+        },
+    })
 }
 
 pub fn to_big_endian(bytes: &[u8]) -> Vec<u8> {
@@ -16,9 +73,9 @@ pub fn bytes_to_hex(bytes: &[u8]) -> String {
     bytes.iter().map(|f| format!("{:02x}", f)).collect()
 }
 
-pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, hex::FromHexError> {
+pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, Error> {
     // TODO: Implement conversion of hex string to bytes vector
-    decode(hex)
+    decode_hex(hex)
 }
 
 pub fn swap_endian_u32(num: u32) -> [u8; 4] {
@@ -28,90 +85,102 @@ pub fn swap_endian_u32(num: u32) -> [u8; 4] {
     version
 }
 
-pub fn parse_satoshis(input: &str) -> Result<u64, String> {
+pub fn parse_satoshis(input: &str) -> Result<Amount, Error> {
     // TODO: Parse input string to u64, return error string if invalid
-    input
-        .parse()
-        .map_err(|_| "Invalid satoshi amount".to_string())
+    // Plain integers are satoshis; see `Amount::from_str` for the rest.
+    input.parse()
 }
 
-#[derive(PartialEq, Eq)]
-pub enum ScriptType {
-    P2PKH,
-    P2WPKH,
-    Unknown,
-}
-
-pub fn classify_script(script: &[u8]) -> ScriptType {
-    // TODO: Match script pattern and return corresponding ScriptType
-    // P2PKH: 76 a9 14 [20 bytes] 88 ac (25 bytes total)
-    match script {
-        [0x76, 0xa9, 0x14] => ScriptType::P2PKH,
+// TODO: complete Outpoint tuple struct
+/// A reference to output `.1` of transaction `.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Outpoint(pub Txid, pub u32);
 
-        // P2WPKH: 00 14 [20 bytes] (22 bytes total)
-        [0x00, 0x14, 0xff] => ScriptType::P2WPKH,
+impl Outpoint {
+    /// What coinbase inputs spend: an all-zero txid and vout `0xffffffff`.
+    pub const NULL: Outpoint = Outpoint(Txid::all_zeros(), u32::MAX);
 
-        // Tout autre pattern
-        _ => ScriptType::Unknown,
+    pub fn is_null(&self) -> bool {
+        *self == Self::NULL
     }
-}
 
-// TODO: complete Outpoint tuple struct
-pub struct Outpoint(pub String, pub u32);
+    /// Consensus serialization: the txid in internal byte order, then the
+    /// vout as little-endian.
+    pub fn to_bytes(&self) -> [u8; 36] {
+        let mut bytes = [0; 36];
+        bytes[..32].copy_from_slice(self.0.as_byte_array());
+        bytes[32..].copy_from_slice(&self.1.to_le_bytes());
+        bytes
+    }
 
-pub fn read_pushdata(script: &[u8]) -> &[u8] {
-    // TODO: Return the pushdata portion of the script slice (assumes pushdata starts at index 2)
-    // Version simple : assume que les données commencent à l'index 2
-    // (après opcode + longueur)
-    if script.len() <= 2 {
-        return &[];
+    pub fn from_bytes(bytes: &[u8]) -> Result<Outpoint, Error> {
+        if bytes.len() != 36 {
+            return Err(Error::InvalidOutpointLength {
+                length: bytes.len(),
+            });
+        }
+        let (txid, vout) = bytes.split_at(32);
+        Ok(Outpoint(
+            Txid::from_slice(txid)?,
+            u32::from_le_bytes(vout.try_into().unwrap()),
+        ))
     }
-    &script[2..]
 }
 
-pub trait Wallet {
-    fn balance(&self) -> u64;
+impl fmt::Display for Outpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.0, self.1)
+    }
 }
 
-pub struct TestWallet {
-    pub confirmed: u64,
-}
+impl FromStr for Outpoint {
+    type Err = Error;
 
-impl Wallet for TestWallet {
-    fn balance(&self) -> u64 {
-        // TODO: Return the wallet's confirmed balance
-        self.confirmed
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidOutpoint {
+            input: input.to_string(),
+        };
+        let (txid, vout) = input.split_once(':').ok_or_else(invalid)?;
+        // Reject the signs and padding `u32::from_str` would let through.
+        if vout.is_empty() || !vout.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        Ok(Outpoint(
+            txid.parse()?,
+            vout.parse().map_err(|_| invalid())?,
+        ))
     }
 }
 
-pub fn apply_fee(balance: &mut u64, fee: u64) {
-    // TODO: Subtract fee from mutable balance reference
-    let diff = *balance - fee;
-    *balance = diff;
+pub fn read_pushdata(script: &[u8]) -> &[u8] {
+    // TODO: Return the pushdata portion of the script slice (assumes pushdata starts at index 2)
+    // Returns the first non-empty push, so a leading OP_0 witness version is
+    // skipped. Malformed scripts yield an empty slice.
+    instructions(script)
+        .map_while(Result::ok)
+        .find_map(|ins| match ins {
+            Instruction::Push(data) if !data.is_empty() => Some(data),
+            _ => None,
+        })
+        .unwrap_or(&[])
 }
 
-pub fn move_txid(txid: String) -> String {
-    // TODO: Return formatted string including the txid for display or logging
-    format!("txid: {}", txid)
+/// Blocks a coinbase output must wait before it can be spent.
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// Deducts `fee` from `balance`, leaving it untouched if the fee is larger.
+pub fn apply_fee(balance: &mut Amount, fee: Amount) -> Result<(), Error> {
+    // TODO: Subtract fee from mutable balance reference
+    *balance = balance.checked_sub(fee).ok_or(Error::InsufficientFunds {
+        available: *balance,
+        required: fee,
+    })?;
+    Ok(())
 }
 
-// TODO: Add necessary derive traits
-#[derive(Debug, PartialEq, Eq)]
-pub enum Opcode {
-    OpChecksig,
-    OpDup,
-    OpInvalid,
-}
-
-impl Opcode {
-    pub fn from_byte(byte: u8) -> Result<Self, String> {
-        // TODO: Implement mapping from byte to Opcode variant
-        match byte {
-            0x76 => Ok(Opcode::OpDup),
-            0xac => Ok(Opcode::OpChecksig),
-            _ => Err("Invalid opcode: 0x00".to_string()),
-        }
-    }
+pub fn move_txid(txid: Txid) -> String {
+    // TODO: Return formatted string including the txid for display or logging
+    format!("txid: {txid}")
 }
 
 // TODO: Add necessary derive traits
@@ -122,21 +191,71 @@ pub trait UTXOfunc {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct UTXO {
-    pub txid: Vec<u8>,
+    pub txid: Txid,
     pub vout: u32,
-    pub value: u64,
+    pub value: Amount,
+    pub script_pubkey: Vec<u8>,
+    /// Height of the block that created the output, or
+    /// [`UTXO::MEMPOOL_HEIGHT`] while it is unconfirmed.
+    pub height: u32,
+    pub is_coinbase: bool,
+}
+
+impl UTXO {
+    /// The height Core gives coins that only exist in the mempool.
+    pub const MEMPOOL_HEIGHT: u32 = 0x7fff_ffff;
+
+    pub fn outpoint(&self) -> Outpoint {
+        Outpoint(self.txid, self.vout)
+    }
+
+    /// Blocks since and including the one that created the output, or zero
+    /// if it is unconfirmed or above `tip_height`.
+    pub fn confirmations(&self, tip_height: u32) -> u32 {
+        match tip_height.checked_sub(self.height) {
+            Some(depth) if self.height != Self::MEMPOOL_HEIGHT => depth.saturating_add(1),
+            _ => 0,
+        }
+    }
+
+    /// Whether the output may be spent in the block after `tip_height`.
+    /// Only coinbase outputs wait, for [`COINBASE_MATURITY`] blocks.
+    pub fn is_mature(&self, tip_height: u32) -> bool {
+        !self.is_coinbase || self.confirmations(tip_height) >= COINBASE_MATURITY
+    }
+
+    /// Whether the output is too small to be worth spending at
+    /// `dust_relay_fee`; see [`is_dust`].
+    pub fn is_dust(&self, dust_relay_fee: FeeRate) -> bool {
+        is_dust(self.value, &self.script_pubkey, dust_relay_fee)
+    }
 }
 
 impl UTXOfunc for UTXO {
     fn depense(&self) -> Self {
         UTXO {
-            txid: self.txid.clone(),
+            txid: self.txid,
             vout: self.vout,
             value: self.value,
+            script_pubkey: self.script_pubkey.clone(),
+            height: self.height,
+            is_coinbase: self.is_coinbase,
         }
     }
 }
 
+impl From<&UTXO> for Outpoint {
+    fn from(utxo: &UTXO) -> Outpoint {
+        Outpoint(utxo.txid, utxo.vout)
+    }
+}
+
+impl From<UTXO> for Outpoint {
+    fn from(utxo: UTXO) -> Outpoint {
+        Outpoint::from(&utxo)
+    }
+}
+
 pub fn consume_utxo(utxo: UTXO) -> UTXO {
     // TODO: Implement UTXO consumption logic (if any)
     utxo.depense()
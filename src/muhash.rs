@@ -0,0 +1,225 @@
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+
+use crate::{UtxoSetHash, sha256};
+
+const LIMBS: usize = 48;
+const BYTES: usize = LIMBS * 8;
+/// The modulus is `2^3072 - MAX_PRIME_DIFF`, the largest 3072-bit safe prime.
+const MAX_PRIME_DIFF: u64 = 1_103_717;
+
+/// A rolling hash of a set of byte strings, following Core's `MuHash3072`:
+/// each element maps to a number modulo a 3072-bit prime, and the set hashes
+/// to their product. Elements can be added and removed in any order, and
+/// two sets with the same elements hash the same however they were built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuHash3072 {
+    numerator: Num3072,
+    denominator: Num3072,
+}
+
+impl MuHash3072 {
+    /// The hash of the empty set.
+    pub fn new() -> Self {
+        MuHash3072 {
+            numerator: Num3072::ONE,
+            denominator: Num3072::ONE,
+        }
+    }
+
+    pub fn insert(&mut self, data: &[u8]) {
+        self.numerator = self.numerator.mul(&Num3072::from_data(data));
+    }
+
+    /// Removals are kept apart and only divided out on [`finalize`], which
+    /// keeps both operations a single multiplication.
+    ///
+    /// [`finalize`]: Self::finalize
+    pub fn remove(&mut self, data: &[u8]) {
+        self.denominator = self.denominator.mul(&Num3072::from_data(data));
+    }
+
+    /// The SHA256 of the set's product, as `gettxoutsetinfo` reports it
+    /// under `muhash`.
+    pub fn finalize(&self) -> UtxoSetHash {
+        let product = self.numerator.mul(&self.denominator.inverse());
+        UtxoSetHash::from_byte_array(sha256(&product.to_le_bytes()))
+    }
+}
+
+impl Default for MuHash3072 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A number below the modulus, as little-endian 64-bit limbs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Num3072([u64; LIMBS]);
+
+impl Num3072 {
+    const ONE: Num3072 = {
+        let mut limbs = [0; LIMBS];
+        limbs[0] = 1;
+        Num3072(limbs)
+    };
+
+    /// Core's `ToNum3072`: the ChaCha20 keystream keyed with the SHA256 of
+    /// `data`, read as a little-endian number.
+    fn from_data(data: &[u8]) -> Self {
+        let mut bytes = [0u8; BYTES];
+        ChaCha20::new(&sha256(data).into(), &[0; 12].into()).apply_keystream(&mut bytes);
+        let mut limbs = [0; LIMBS];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        reduce(&mut limbs, 0);
+        Num3072(limbs)
+    }
+
+    fn to_le_bytes(self) -> [u8; BYTES] {
+        let mut bytes = [0; BYTES];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn mul(&self, other: &Num3072) -> Num3072 {
+        let mut product = [0u64; 2 * LIMBS];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.0.iter().enumerate() {
+                let t = u128::from(product[i + j]) + u128::from(a) * u128::from(b) + carry;
+                product[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            product[i + LIMBS] = carry as u64;
+        }
+
+        // 2^3072 is congruent to MAX_PRIME_DIFF, so fold the high half down.
+        let mut limbs = [0; LIMBS];
+        let mut carry = 0u128;
+        for i in 0..LIMBS {
+            let t = u128::from(product[i])
+                + u128::from(product[i + LIMBS]) * u128::from(MAX_PRIME_DIFF)
+                + carry;
+            limbs[i] = t as u64;
+            carry = t >> 64;
+        }
+        reduce(&mut limbs, carry as u64);
+        Num3072(limbs)
+    }
+
+    /// The inverse by the binary extended Euclidean algorithm, which only
+    /// needs shifts, additions and subtractions. Zero has none and stays
+    /// zero.
+    fn inverse(&self) -> Num3072 {
+        if self.0 == [0; LIMBS] {
+            return *self;
+        }
+        let (mut u, mut v) = (self.0, MODULUS);
+        let (mut x1, mut x2) = (Num3072::ONE.0, [0; LIMBS]);
+        // Invariants: x1 * self = u and x2 * self = v, modulo the prime.
+        while u != Num3072::ONE.0 && v != Num3072::ONE.0 {
+            while u[0] & 1 == 0 {
+                shr1(&mut u, false);
+                halve_mod(&mut x1);
+            }
+            while v[0] & 1 == 0 {
+                shr1(&mut v, false);
+                halve_mod(&mut x2);
+            }
+            if ge(&u, &v) {
+                sub(&mut u, &v);
+                sub_mod(&mut x1, &x2);
+            } else {
+                sub(&mut v, &u);
+                sub_mod(&mut x2, &x1);
+            }
+        }
+        Num3072(if u == Num3072::ONE.0 { x1 } else { x2 })
+    }
+}
+
+/// `2^3072 - MAX_PRIME_DIFF` in limbs.
+const MODULUS: [u64; LIMBS] = {
+    let mut limbs = [u64::MAX; LIMBS];
+    limbs[0] = u64::MAX - MAX_PRIME_DIFF + 1;
+    limbs
+};
+
+fn add(a: &mut [u64; LIMBS], b: &[u64; LIMBS]) -> bool {
+    let mut carry = false;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let (sum, c1) = x.overflowing_add(y);
+        let (sum, c2) = sum.overflowing_add(u64::from(carry));
+        *x = sum;
+        carry = c1 || c2;
+    }
+    carry
+}
+
+fn sub(a: &mut [u64; LIMBS], b: &[u64; LIMBS]) -> bool {
+    let mut borrow = false;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let (diff, b1) = x.overflowing_sub(y);
+        let (diff, b2) = diff.overflowing_sub(u64::from(borrow));
+        *x = diff;
+        borrow = b1 || b2;
+    }
+    borrow
+}
+
+fn ge(a: &[u64; LIMBS], b: &[u64; LIMBS]) -> bool {
+    a.iter().rev().cmp(b.iter().rev()).is_ge()
+}
+
+/// Shifts right by one, bringing `top` in as the highest bit.
+fn shr1(a: &mut [u64; LIMBS], top: bool) {
+    let mut carry = top;
+    for limb in a.iter_mut().rev() {
+        let low = *limb & 1 == 1;
+        *limb = (*limb >> 1) | (u64::from(carry) << 63);
+        carry = low;
+    }
+}
+
+/// Halves a number below the modulus, adding the modulus first if odd.
+fn halve_mod(a: &mut [u64; LIMBS]) {
+    let carry = a[0] & 1 == 1 && add(a, &MODULUS);
+    shr1(a, carry);
+}
+
+/// Subtracts two numbers below the modulus, wrapping around it.
+fn sub_mod(a: &mut [u64; LIMBS], b: &[u64; LIMBS]) {
+    if sub(a, b) {
+        add(a, &MODULUS);
+    }
+}
+
+/// Brings `high * 2^3072 + limbs` below the modulus.
+fn reduce(limbs: &mut [u64; LIMBS], mut high: u64) {
+    while high != 0 {
+        let mut carry = u128::from(high) * u128::from(MAX_PRIME_DIFF);
+        for limb in limbs.iter_mut() {
+            let t = u128::from(*limb) + carry;
+            *limb = t as u64;
+            carry = t >> 64;
+        }
+        high = carry as u64;
+    }
+    // The number is now below 2^3072, so at most one subtraction of the
+    // modulus is needed: adding MAX_PRIME_DIFF overflows exactly when the
+    // number is at least the modulus, and what remains is the difference.
+    let mut reduced = *limbs;
+    let mut carry = u128::from(MAX_PRIME_DIFF);
+    for limb in reduced.iter_mut() {
+        let t = u128::from(*limb) + carry;
+        *limb = t as u64;
+        carry = t >> 64;
+    }
+    if carry != 0 {
+        *limbs = reduced;
+    }
+}
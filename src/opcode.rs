@@ -0,0 +1,272 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+macro_rules! opcodes {
+    ($($variant:ident = $byte:literal => $name:literal,)*) => {
+        /// A Bitcoin script opcode.
+        ///
+        /// Every byte except the undefined range `0xbb..=0xfe` maps to exactly
+        /// one variant. The direct pushes `0x01..=0x4b` share `OpPushBytes`,
+        /// which carries the number of bytes pushed.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Opcode {
+            OpPushBytes(u8),
+            $($variant,)*
+        }
+
+        impl Opcode {
+            pub fn from_byte(byte: u8) -> Result<Self, Error> {
+                match byte {
+                    0x01..=0x4b => Ok(Opcode::OpPushBytes(byte)),
+                    $($byte => Ok(Opcode::$variant),)*
+                    _ => Err(Error::InvalidOpcode { byte }),
+                }
+            }
+
+            pub fn to_byte(self) -> u8 {
+                match self {
+                    Opcode::OpPushBytes(len) => len,
+                    $(Opcode::$variant => $byte,)*
+                }
+            }
+
+            fn static_name(self) -> Option<&'static str> {
+                match self {
+                    Opcode::OpPushBytes(_) => None,
+                    $(Opcode::$variant => Some($name),)*
+                }
+            }
+
+            fn from_static_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Opcode::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    // push value
+    Op0 = 0x00 => "OP_0",
+    OpPushdata1 = 0x4c => "OP_PUSHDATA1",
+    OpPushdata2 = 0x4d => "OP_PUSHDATA2",
+    OpPushdata4 = 0x4e => "OP_PUSHDATA4",
+    Op1negate = 0x4f => "OP_1NEGATE",
+    OpReserved = 0x50 => "OP_RESERVED",
+    Op1 = 0x51 => "OP_1",
+    Op2 = 0x52 => "OP_2",
+    Op3 = 0x53 => "OP_3",
+    Op4 = 0x54 => "OP_4",
+    Op5 = 0x55 => "OP_5",
+    Op6 = 0x56 => "OP_6",
+    Op7 = 0x57 => "OP_7",
+    Op8 = 0x58 => "OP_8",
+    Op9 = 0x59 => "OP_9",
+    Op10 = 0x5a => "OP_10",
+    Op11 = 0x5b => "OP_11",
+    Op12 = 0x5c => "OP_12",
+    Op13 = 0x5d => "OP_13",
+    Op14 = 0x5e => "OP_14",
+    Op15 = 0x5f => "OP_15",
+    Op16 = 0x60 => "OP_16",
+
+    // control
+    OpNop = 0x61 => "OP_NOP",
+    OpVer = 0x62 => "OP_VER",
+    OpIf = 0x63 => "OP_IF",
+    OpNotif = 0x64 => "OP_NOTIF",
+    OpVerif = 0x65 => "OP_VERIF",
+    OpVernotif = 0x66 => "OP_VERNOTIF",
+    OpElse = 0x67 => "OP_ELSE",
+    OpEndif = 0x68 => "OP_ENDIF",
+    OpVerify = 0x69 => "OP_VERIFY",
+    OpReturn = 0x6a => "OP_RETURN",
+
+    // stack ops
+    OpToaltstack = 0x6b => "OP_TOALTSTACK",
+    OpFromaltstack = 0x6c => "OP_FROMALTSTACK",
+    Op2drop = 0x6d => "OP_2DROP",
+    Op2dup = 0x6e => "OP_2DUP",
+    Op3dup = 0x6f => "OP_3DUP",
+    Op2over = 0x70 => "OP_2OVER",
+    Op2rot = 0x71 => "OP_2ROT",
+    Op2swap = 0x72 => "OP_2SWAP",
+    OpIfdup = 0x73 => "OP_IFDUP",
+    OpDepth = 0x74 => "OP_DEPTH",
+    OpDrop = 0x75 => "OP_DROP",
+    OpDup = 0x76 => "OP_DUP",
+    OpNip = 0x77 => "OP_NIP",
+    OpOver = 0x78 => "OP_OVER",
+    OpPick = 0x79 => "OP_PICK",
+    OpRoll = 0x7a => "OP_ROLL",
+    OpRot = 0x7b => "OP_ROT",
+    OpSwap = 0x7c => "OP_SWAP",
+    OpTuck = 0x7d => "OP_TUCK",
+
+    // splice ops
+    OpCat = 0x7e => "OP_CAT",
+    OpSubstr = 0x7f => "OP_SUBSTR",
+    OpLeft = 0x80 => "OP_LEFT",
+    OpRight = 0x81 => "OP_RIGHT",
+    OpSize = 0x82 => "OP_SIZE",
+
+    // bit logic
+    OpInvert = 0x83 => "OP_INVERT",
+    OpAnd = 0x84 => "OP_AND",
+    OpOr = 0x85 => "OP_OR",
+    OpXor = 0x86 => "OP_XOR",
+    OpEqual = 0x87 => "OP_EQUAL",
+    OpEqualverify = 0x88 => "OP_EQUALVERIFY",
+    OpReserved1 = 0x89 => "OP_RESERVED1",
+    OpReserved2 = 0x8a => "OP_RESERVED2",
+
+    // numeric
+    Op1add = 0x8b => "OP_1ADD",
+    Op1sub = 0x8c => "OP_1SUB",
+    Op2mul = 0x8d => "OP_2MUL",
+    Op2div = 0x8e => "OP_2DIV",
+    OpNegate = 0x8f => "OP_NEGATE",
+    OpAbs = 0x90 => "OP_ABS",
+    OpNot = 0x91 => "OP_NOT",
+    Op0notequal = 0x92 => "OP_0NOTEQUAL",
+    OpAdd = 0x93 => "OP_ADD",
+    OpSub = 0x94 => "OP_SUB",
+    OpMul = 0x95 => "OP_MUL",
+    OpDiv = 0x96 => "OP_DIV",
+    OpMod = 0x97 => "OP_MOD",
+    OpLshift = 0x98 => "OP_LSHIFT",
+    OpRshift = 0x99 => "OP_RSHIFT",
+    OpBooland = 0x9a => "OP_BOOLAND",
+    OpBoolor = 0x9b => "OP_BOOLOR",
+    OpNumequal = 0x9c => "OP_NUMEQUAL",
+    OpNumequalverify = 0x9d => "OP_NUMEQUALVERIFY",
+    OpNumnotequal = 0x9e => "OP_NUMNOTEQUAL",
+    OpLessthan = 0x9f => "OP_LESSTHAN",
+    OpGreaterthan = 0xa0 => "OP_GREATERTHAN",
+    OpLessthanorequal = 0xa1 => "OP_LESSTHANOREQUAL",
+    OpGreaterthanorequal = 0xa2 => "OP_GREATERTHANOREQUAL",
+    OpMin = 0xa3 => "OP_MIN",
+    OpMax = 0xa4 => "OP_MAX",
+    OpWithin = 0xa5 => "OP_WITHIN",
+
+    // crypto
+    OpRipemd160 = 0xa6 => "OP_RIPEMD160",
+    OpSha1 = 0xa7 => "OP_SHA1",
+    OpSha256 = 0xa8 => "OP_SHA256",
+    OpHash160 = 0xa9 => "OP_HASH160",
+    OpHash256 = 0xaa => "OP_HASH256",
+    OpCodeseparator = 0xab => "OP_CODESEPARATOR",
+    OpChecksig = 0xac => "OP_CHECKSIG",
+    OpChecksigverify = 0xad => "OP_CHECKSIGVERIFY",
+    OpCheckmultisig = 0xae => "OP_CHECKMULTISIG",
+    OpCheckmultisigverify = 0xaf => "OP_CHECKMULTISIGVERIFY",
+
+    // expansion
+    OpNop1 = 0xb0 => "OP_NOP1",
+    OpChecklocktimeverify = 0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+    OpChecksequenceverify = 0xb2 => "OP_CHECKSEQUENCEVERIFY",
+    OpNop4 = 0xb3 => "OP_NOP4",
+    OpNop5 = 0xb4 => "OP_NOP5",
+    OpNop6 = 0xb5 => "OP_NOP6",
+    OpNop7 = 0xb6 => "OP_NOP7",
+    OpNop8 = 0xb7 => "OP_NOP8",
+    OpNop9 = 0xb8 => "OP_NOP9",
+    OpNop10 = 0xb9 => "OP_NOP10",
+
+    // tapscript
+    OpChecksigadd = 0xba => "OP_CHECKSIGADD",
+
+    OpInvalid = 0xff => "OP_INVALIDOPCODE",
+}
+
+impl Opcode {
+    /// True for opcodes that only push data or a small number onto the stack.
+    pub fn is_push(self) -> bool {
+        let byte = self.to_byte();
+        byte <= 0x60 && self != Opcode::OpReserved
+    }
+
+    /// True for opcodes disabled since 2010, which fail a script even when
+    /// they sit in an unexecuted branch.
+    pub fn is_disabled(self) -> bool {
+        matches!(
+            self,
+            Opcode::OpCat
+                | Opcode::OpSubstr
+                | Opcode::OpLeft
+                | Opcode::OpRight
+                | Opcode::OpInvert
+                | Opcode::OpAnd
+                | Opcode::OpOr
+                | Opcode::OpXor
+                | Opcode::Op2mul
+                | Opcode::Op2div
+                | Opcode::OpMul
+                | Opcode::OpDiv
+                | Opcode::OpMod
+                | Opcode::OpLshift
+                | Opcode::OpRshift
+        )
+    }
+
+    /// True for the flow-control opcodes that open, switch or close a branch.
+    pub fn is_conditional(self) -> bool {
+        matches!(
+            self,
+            Opcode::OpIf | Opcode::OpNotif | Opcode::OpElse | Opcode::OpEndif
+        )
+    }
+
+    /// True if this opcode is an `OP_SUCCESSx` under tapscript (BIP 342).
+    pub fn is_success(self) -> bool {
+        Opcode::is_success_byte(self.to_byte())
+    }
+
+    /// Same as [`Opcode::is_success`] but for a raw byte, since the undefined
+    /// range `0xbb..=0xfe` has no `Opcode` variant yet is `OP_SUCCESSx`.
+    pub fn is_success_byte(byte: u8) -> bool {
+        matches!(
+            byte,
+            0x50 | 0x62 | 0x7e..=0x81 | 0x83..=0x86 | 0x89..=0x8a | 0x8d..=0x8e | 0x95..=0x99 | 0xbb..=0xfe
+        )
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.static_name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "OP_PUSHBYTES_{}", self.to_byte()),
+        }
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = Error;
+
+    /// Parses a canonical name such as `OP_DUP`, plus the common aliases
+    /// `OP_FALSE`, `OP_TRUE`, `OP_NOP2` and `OP_NOP3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let opcode = match s {
+            "OP_FALSE" => Some(Opcode::Op0),
+            "OP_TRUE" => Some(Opcode::Op1),
+            "OP_NOP2" => Some(Opcode::OpChecklocktimeverify),
+            "OP_NOP3" => Some(Opcode::OpChecksequenceverify),
+            _ => match s.strip_prefix("OP_PUSHBYTES_") {
+                Some(len) => len
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|len| (0x01..=0x4b).contains(len))
+                    .map(Opcode::OpPushBytes),
+                None => Opcode::from_static_name(s),
+            },
+        };
+        opcode.ok_or_else(|| Error::InvalidOpcodeName {
+            name: s.to_string(),
+        })
+    }
+}
@@ -0,0 +1,443 @@
+use crate::{Error, MAX_SCRIPT_SIZE, Opcode, ScriptError};
+
+/// A single parsed script element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    /// A non-push opcode.
+    Op(Opcode),
+    /// Data pushed by `OP_0`, a direct push or `OP_PUSHDATA1/2/4`.
+    Push(&'a [u8]),
+}
+
+/// Iterator over the instructions of a script.
+///
+/// A truncated push ends the iteration after its error. An undefined opcode
+/// yields [`Error::InvalidOpcode`] but parsing continues behind it, since
+/// such bytes are only fatal when executed.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    pos: usize,
+}
+
+pub fn instructions(script: &[u8]) -> Instructions<'_> {
+    Instructions { script, pos: 0 }
+}
+
+impl<'a> Instructions<'a> {
+    /// Offset of the next instruction to be read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn take(&mut self, offset: usize, len: usize) -> Result<&'a [u8], Error> {
+        let available = self.script.len() - self.pos;
+        if len > available {
+            self.pos = self.script.len();
+            return Err(Error::TruncatedPush {
+                offset,
+                expected: len,
+                available,
+            });
+        }
+        let data = &self.script[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn take_len(&mut self, offset: usize, width: usize) -> Result<usize, Error> {
+        let bytes = self.take(offset, width)?;
+        let mut buf = [0u8; 4];
+        buf[..width].copy_from_slice(bytes);
+        Ok(u32::from_le_bytes(buf) as usize)
+    }
+
+    fn read(&mut self) -> Result<Instruction<'a>, Error> {
+        let offset = self.pos;
+        let byte = self.script[offset];
+        self.pos += 1;
+
+        let len = match Opcode::from_byte(byte)? {
+            Opcode::Op0 => 0,
+            Opcode::OpPushBytes(len) => len as usize,
+            Opcode::OpPushdata1 => self.take_len(offset, 1)?,
+            Opcode::OpPushdata2 => self.take_len(offset, 2)?,
+            Opcode::OpPushdata4 => self.take_len(offset, 4)?,
+            op => return Ok(Instruction::Op(op)),
+        };
+        self.take(offset, len).map(Instruction::Push)
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.script.len() {
+            return None;
+        }
+        Some(self.read())
+    }
+}
+
+/// Standard scriptPubKey templates, as recognised by Bitcoin Core's `Solver`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    /// `<pubkey> OP_CHECKSIG`
+    P2PK,
+    /// `OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG`
+    P2PKH,
+    /// `OP_HASH160 <20 bytes> OP_EQUAL`
+    P2SH,
+    /// `OP_0 <20 bytes>`
+    P2WPKH,
+    /// `OP_0 <32 bytes>`
+    P2WSH,
+    /// `OP_1 <32 bytes>`
+    P2TR,
+    /// Pay-to-anchor, `OP_1 <4e73>`
+    P2A,
+    /// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`
+    Multisig,
+    /// `OP_RETURN` followed by push-only data
+    NullData,
+    /// A witness program of version 1 to 16 not covered above
+    WitnessUnknown,
+    Unknown,
+}
+
+pub fn classify_script(script: &[u8]) -> ScriptType {
+    decode_script_pubkey(script).script_type()
+}
+
+/// A classified scriptPubKey together with the data it commits to, borrowed
+/// from the script it was decoded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptPubKey<'a> {
+    P2PK {
+        pubkey: &'a [u8],
+    },
+    P2PKH {
+        pubkey_hash: &'a [u8; 20],
+    },
+    P2SH {
+        script_hash: &'a [u8; 20],
+    },
+    P2WPKH {
+        pubkey_hash: &'a [u8; 20],
+    },
+    P2WSH {
+        script_hash: &'a [u8; 32],
+    },
+    P2TR {
+        output_key: &'a [u8; 32],
+    },
+    P2A,
+    Multisig {
+        required: u8,
+        pubkeys: Vec<&'a [u8]>,
+    },
+    /// `data` is everything after `OP_RETURN`, still push-encoded.
+    NullData {
+        data: &'a [u8],
+    },
+    WitnessUnknown {
+        version: u8,
+        program: &'a [u8],
+    },
+    Unknown(&'a [u8]),
+}
+
+pub fn decode_script_pubkey(script: &[u8]) -> ScriptPubKey<'_> {
+    if let [0x51, 0x02, 0x4e, 0x73] = script {
+        return ScriptPubKey::P2A;
+    }
+    if let Some((version, program)) = witness_program(script) {
+        return match (version, program.len()) {
+            (0, 20) => ScriptPubKey::P2WPKH {
+                pubkey_hash: program.try_into().unwrap(),
+            },
+            (0, 32) => ScriptPubKey::P2WSH {
+                script_hash: program.try_into().unwrap(),
+            },
+            // Any other version 0 length is unspendable.
+            (0, _) => ScriptPubKey::Unknown(script),
+            (1, 32) => ScriptPubKey::P2TR {
+                output_key: program.try_into().unwrap(),
+            },
+            _ => ScriptPubKey::WitnessUnknown { version, program },
+        };
+    }
+    match script {
+        [0xa9, 0x14, hash @ .., 0x87] if hash.len() == 20 => ScriptPubKey::P2SH {
+            script_hash: hash.try_into().unwrap(),
+        },
+        [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] if hash.len() == 20 => ScriptPubKey::P2PKH {
+            pubkey_hash: hash.try_into().unwrap(),
+        },
+        [len, key @ .., 0xac] if *len as usize == key.len() && is_pubkey(key) => {
+            ScriptPubKey::P2PK { pubkey: key }
+        }
+        [0x6a, data @ ..] if is_push_only(data) => ScriptPubKey::NullData { data },
+        _ => match multisig_keys(script) {
+            Some((required, pubkeys)) => ScriptPubKey::Multisig { required, pubkeys },
+            None => ScriptPubKey::Unknown(script),
+        },
+    }
+}
+
+impl ScriptPubKey<'_> {
+    pub fn script_type(&self) -> ScriptType {
+        match self {
+            ScriptPubKey::P2PK { .. } => ScriptType::P2PK,
+            ScriptPubKey::P2PKH { .. } => ScriptType::P2PKH,
+            ScriptPubKey::P2SH { .. } => ScriptType::P2SH,
+            ScriptPubKey::P2WPKH { .. } => ScriptType::P2WPKH,
+            ScriptPubKey::P2WSH { .. } => ScriptType::P2WSH,
+            ScriptPubKey::P2TR { .. } => ScriptType::P2TR,
+            ScriptPubKey::P2A => ScriptType::P2A,
+            ScriptPubKey::Multisig { .. } => ScriptType::Multisig,
+            ScriptPubKey::NullData { .. } => ScriptType::NullData,
+            ScriptPubKey::WitnessUnknown { .. } => ScriptType::WitnessUnknown,
+            ScriptPubKey::Unknown(_) => ScriptType::Unknown,
+        }
+    }
+
+    /// Rebuilds the exact scriptPubKey this value was decoded from.
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = Vec::new();
+        match self {
+            ScriptPubKey::P2PK { pubkey } => {
+                script.push(pubkey.len() as u8);
+                script.extend_from_slice(pubkey);
+                script.push(0xac);
+            }
+            ScriptPubKey::P2PKH { pubkey_hash } => {
+                script.extend_from_slice(&[0x76, 0xa9, 0x14]);
+                script.extend_from_slice(&pubkey_hash[..]);
+                script.extend_from_slice(&[0x88, 0xac]);
+            }
+            ScriptPubKey::P2SH { script_hash } => {
+                script.extend_from_slice(&[0xa9, 0x14]);
+                script.extend_from_slice(&script_hash[..]);
+                script.push(0x87);
+            }
+            ScriptPubKey::P2WPKH { pubkey_hash } => {
+                script.extend_from_slice(&[0x00, 0x14]);
+                script.extend_from_slice(&pubkey_hash[..]);
+            }
+            ScriptPubKey::P2WSH { script_hash } => {
+                script.extend_from_slice(&[0x00, 0x20]);
+                script.extend_from_slice(&script_hash[..]);
+            }
+            ScriptPubKey::P2TR { output_key } => {
+                script.extend_from_slice(&[0x51, 0x20]);
+                script.extend_from_slice(&output_key[..]);
+            }
+            ScriptPubKey::P2A => script.extend_from_slice(&[0x51, 0x02, 0x4e, 0x73]),
+            ScriptPubKey::Multisig { required, pubkeys } => {
+                script.push(0x50 + required);
+                for key in pubkeys {
+                    script.push(key.len() as u8);
+                    script.extend_from_slice(key);
+                }
+                script.push(0x50 + pubkeys.len() as u8);
+                script.push(0xae);
+            }
+            ScriptPubKey::NullData { data } => {
+                script.push(0x6a);
+                script.extend_from_slice(data);
+            }
+            ScriptPubKey::WitnessUnknown { version, program } => {
+                script.push(0x50 + version);
+                script.push(program.len() as u8);
+                script.extend_from_slice(program);
+            }
+            ScriptPubKey::Unknown(raw) => script.extend_from_slice(raw),
+        }
+        script
+    }
+}
+
+/// True if the script parses and contains nothing but pushes, using Core's
+/// definition where every opcode up to `OP_16` counts.
+pub fn is_push_only(script: &[u8]) -> bool {
+    let mut iter = instructions(script);
+    loop {
+        let offset = iter.position();
+        match iter.next() {
+            None => return true,
+            Some(Ok(_)) if script[offset] <= 0x60 => {}
+            Some(_) => return false,
+        }
+    }
+}
+
+/// Core's `CScript::IsUnspendable`: outputs that can never be spent, and so
+/// never enter the UTXO set.
+pub fn is_unspendable(script: &[u8]) -> bool {
+    script.first() == Some(&Opcode::OpReturn.to_byte()) || script.len() > MAX_SCRIPT_SIZE
+}
+
+/// Splits a BIP 141 witness program into its version and program bytes.
+pub(crate) fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    let version = match script.first()? {
+        0x00 => 0,
+        byte @ 0x51..=0x60 => byte - 0x50,
+        _ => return None,
+    };
+    match script {
+        [_, len, program @ ..]
+            if (4..=42).contains(&script.len()) && *len as usize == program.len() =>
+        {
+            Some((version, program))
+        }
+        _ => None,
+    }
+}
+
+/// Size and header check of a serialized public key, like `CPubKey::ValidSize`.
+pub(crate) fn is_pubkey(key: &[u8]) -> bool {
+    match key.first() {
+        Some(0x02 | 0x03) => key.len() == 33,
+        Some(0x04 | 0x06 | 0x07) => key.len() == 65,
+        _ => false,
+    }
+}
+
+/// Matches bare multisig and returns the required signature count with the
+/// keys. Keys must use direct pushes and `m`, `n` must be `OP_1..=OP_16`.
+pub(crate) fn multisig_keys(script: &[u8]) -> Option<(u8, Vec<&[u8]>)> {
+    let (&first, rest) = script.split_first()?;
+    let (&last, rest) = rest.split_last()?;
+    let (&n_op, mut rest) = rest.split_last()?;
+    if last != 0xae || !(0x51..=0x60).contains(&first) || !(0x51..=0x60).contains(&n_op) {
+        return None;
+    }
+    let (m, n) = (first - 0x50, n_op - 0x50);
+
+    let mut keys = Vec::with_capacity(n as usize);
+    while let Some((&len, tail)) = rest.split_first() {
+        let key = tail.get(..len as usize)?;
+        if !(0x01..=0x4b).contains(&len) || !is_pubkey(key) {
+            return None;
+        }
+        keys.push(key);
+        rest = &tail[len as usize..];
+    }
+    (m <= n && keys.len() == n as usize).then_some((m, keys))
+}
+
+/// Decodes a `CScriptNum`, little-endian with the sign in the top bit of
+/// the last byte, like Core's `CScriptNum(vch, fRequireMinimal,
+/// nMaxNumSize)`. Fails with [`ScriptError::InvalidNumber`] if it is longer
+/// than `max_size` bytes, or than the eight an `i64` holds, or is padded
+/// while `require_minimal` is set.
+pub fn decode_script_num(
+    bytes: &[u8],
+    require_minimal: bool,
+    max_size: usize,
+) -> Result<i64, ScriptError> {
+    if bytes.len() > max_size.min(8) || (require_minimal && !is_minimal_num(bytes)) {
+        return Err(ScriptError::InvalidNumber);
+    }
+    let Some((&last, _)) = bytes.split_last() else {
+        return Ok(0);
+    };
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        value |= u64::from(*byte) << (8 * i);
+    }
+    let magnitude = value & !(0x80u64 << (8 * (bytes.len() - 1)));
+    // Eight bytes can hold a magnitude just past i64::MAX; wrap like Core's
+    // int64_t arithmetic rather than panic.
+    Ok(if last & 0x80 != 0 {
+        (magnitude as i64).wrapping_neg()
+    } else {
+        magnitude as i64
+    })
+}
+
+/// A `CScriptNum` is minimal unless its last byte could be dropped.
+fn is_minimal_num(data: &[u8]) -> bool {
+    match data {
+        [] => true,
+        [.., last] if last & 0x7f != 0 => true,
+        [.., prev, _] => prev & 0x80 != 0,
+        [_] => false,
+    }
+}
+
+/// Encodes `value` as a minimal `CScriptNum`.
+pub fn encode_script_num(value: i64) -> Vec<u8> {
+    let mut out = Vec::new();
+    let negative = value < 0;
+    let mut abs = value.unsigned_abs();
+    while abs > 0 {
+        out.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if let Some(last) = out.last_mut() {
+        if *last & 0x80 != 0 {
+            out.push(if negative { 0x80 } else { 0x00 });
+        } else if negative {
+            *last |= 0x80;
+        }
+    }
+    out
+}
+
+/// Strict DER signature check with trailing sighash byte, as in BIP 66.
+pub(crate) fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    if !(9..=73).contains(&sig.len()) || sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    let r_ok = sig[2] == 0x02
+        && len_r != 0
+        && sig[4] & 0x80 == 0
+        && !(len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0);
+    let s_ok = sig[len_r + 4] == 0x02
+        && len_s != 0
+        && sig[len_r + 6] & 0x80 == 0
+        && !(len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0);
+    r_ok && s_ok
+}
+
+/// Name of a defined legacy sighash type, such as `ALL|ANYONECANPAY`.
+pub(crate) fn sighash_name(sighash: u8) -> Option<&'static str> {
+    Some(match sighash {
+        0x01 => "ALL",
+        0x02 => "NONE",
+        0x03 => "SINGLE",
+        0x81 => "ALL|ANYONECANPAY",
+        0x82 => "NONE|ANYONECANPAY",
+        0x83 => "SINGLE|ANYONECANPAY",
+        _ => return None,
+    })
+}
+
+/// Appends `data` with the smallest push opcode that fits its length.
+pub(crate) fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    let len = data.len();
+    match len {
+        0..=0x4b => script.push(len as u8),
+        0x4c..=0xff => script.extend_from_slice(&[0x4c, len as u8]),
+        0x100..=0xffff => {
+            script.push(0x4d);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        _ => {
+            script.push(0x4e);
+            script.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
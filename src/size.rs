@@ -0,0 +1,209 @@
+use crate::script::{is_unspendable, witness_program};
+use crate::{Amount, FeeRate, ScriptPubKey, ScriptType, WITNESS_SCALE_FACTOR, Weight};
+
+/// A low-S DER signature at its maximum length plus the sighash byte, as
+/// Core assumes when estimating unsigned inputs.
+const ECDSA_SIG_SIZE: usize = 72;
+const SCHNORR_SIG_SIZE: usize = 64;
+const COMPRESSED_PUBKEY_SIZE: usize = 33;
+/// Previous txid and vout.
+const OUTPOINT_SIZE: usize = 36;
+const SEQUENCE_SIZE: usize = 4;
+const VALUE_SIZE: usize = 8;
+
+/// How an input spends its previous output, for estimating its size before
+/// it is signed. Keys are assumed compressed and ECDSA signatures at their
+/// 72-byte maximum, so estimates never fall short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputType {
+    P2PK,
+    P2PKH,
+    P2WPKH,
+    /// P2WPKH wrapped in P2SH.
+    NestedP2WPKH,
+    P2TRKeyPath,
+    /// Bare `m`-of-`n` multisig.
+    Multisig {
+        required: u8,
+        total: u8,
+    },
+    P2SHMultisig {
+        required: u8,
+        total: u8,
+    },
+    P2WSHMultisig {
+        required: u8,
+        total: u8,
+    },
+}
+
+impl InputType {
+    /// The spend implied by an output, where its script alone determines
+    /// it. P2SH and P2WSH outputs hide their redeem script, so they give
+    /// `None` along with non-standard outputs.
+    pub fn from_script_pubkey(script_pubkey: &ScriptPubKey) -> Option<InputType> {
+        match script_pubkey {
+            ScriptPubKey::P2PK { .. } => Some(InputType::P2PK),
+            ScriptPubKey::P2PKH { .. } => Some(InputType::P2PKH),
+            ScriptPubKey::P2WPKH { .. } => Some(InputType::P2WPKH),
+            ScriptPubKey::P2TR { .. } => Some(InputType::P2TRKeyPath),
+            ScriptPubKey::Multisig { required, pubkeys } => Some(InputType::Multisig {
+                required: *required,
+                total: pubkeys.len() as u8,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Size of the scriptSig, without its length prefix.
+    pub fn script_sig_size(self) -> usize {
+        match self {
+            InputType::P2PK => push_size(ECDSA_SIG_SIZE),
+            InputType::P2PKH => push_size(ECDSA_SIG_SIZE) + push_size(COMPRESSED_PUBKEY_SIZE),
+            InputType::P2WPKH | InputType::P2TRKeyPath | InputType::P2WSHMultisig { .. } => 0,
+            // A push of the 22-byte witness program.
+            InputType::NestedP2WPKH => push_size(22),
+            // The leading OP_0 feeds CHECKMULTISIG's extra pop.
+            InputType::Multisig { required, .. } => {
+                1 + required as usize * push_size(ECDSA_SIG_SIZE)
+            }
+            InputType::P2SHMultisig { required, total } => {
+                1 + required as usize * push_size(ECDSA_SIG_SIZE)
+                    + push_size(multisig_script_size(total))
+            }
+        }
+    }
+
+    /// Size of the witness including its item count, or zero for inputs
+    /// that carry none.
+    pub fn witness_size(self) -> usize {
+        match self {
+            InputType::P2PK
+            | InputType::P2PKH
+            | InputType::Multisig { .. }
+            | InputType::P2SHMultisig { .. } => 0,
+            InputType::P2WPKH | InputType::NestedP2WPKH => {
+                1 + item_size(ECDSA_SIG_SIZE) + item_size(COMPRESSED_PUBKEY_SIZE)
+            }
+            InputType::P2TRKeyPath => 1 + item_size(SCHNORR_SIG_SIZE),
+            InputType::P2WSHMultisig { required, total } => {
+                compact_size_len(required as usize + 2)
+                    + item_size(0)
+                    + required as usize * item_size(ECDSA_SIG_SIZE)
+                    + item_size(multisig_script_size(total))
+            }
+        }
+    }
+
+    /// Weight of the scriptSig, with its length prefix, and the witness:
+    /// what signing adds to an input.
+    pub fn satisfaction_weight(self) -> Weight {
+        let script_sig = self.script_sig_size();
+        Weight::from_parts(
+            (compact_size_len(script_sig) + script_sig) as u64,
+            self.witness_size() as u64,
+        )
+    }
+
+    /// Weight of the whole signed input. A transaction with any witness
+    /// input also carries a one-byte empty witness for each input without
+    /// one, and two weight units for the segwit marker and flag.
+    pub fn input_weight(self) -> Weight {
+        let script_sig = self.script_sig_size();
+        Weight::from_parts(
+            (OUTPOINT_SIZE + compact_size_len(script_sig) + script_sig + SEQUENCE_SIZE) as u64,
+            self.witness_size() as u64,
+        )
+    }
+}
+
+impl ScriptType {
+    /// Serialized size of an output of this type, or `None` where the
+    /// script length varies.
+    pub fn output_size(self) -> Option<usize> {
+        let script_len = match self {
+            ScriptType::P2PK => 35,
+            ScriptType::P2PKH => 25,
+            ScriptType::P2SH => 23,
+            ScriptType::P2WPKH => 22,
+            ScriptType::P2WSH | ScriptType::P2TR => 34,
+            ScriptType::P2A => 4,
+            ScriptType::Multisig
+            | ScriptType::NullData
+            | ScriptType::WitnessUnknown
+            | ScriptType::Unknown => return None,
+        };
+        Some(output_size(script_len))
+    }
+}
+
+/// Serialized size of an output whose scriptPubKey is `script_len` bytes.
+pub fn output_size(script_len: usize) -> usize {
+    VALUE_SIZE + compact_size_len(script_len) + script_len
+}
+
+/// Outputs carry no witness data, so their weight is four times their size.
+pub fn output_weight(script_pubkey: &[u8]) -> Weight {
+    Weight::from_wu(output_size(script_pubkey.len()) as u64 * WITNESS_SCALE_FACTOR)
+}
+
+/// The smallest value an output paying to `script_pubkey` can carry without
+/// being dust, following Core's `GetDustThreshold`: the fee, at
+/// `dust_relay_fee`, to create the output and later spend it. Unspendable
+/// outputs have no threshold.
+///
+/// The spend is priced with Core's fixed sizes rather than an
+/// [`InputType`] estimate for the script's type, so thresholds match what
+/// Core relays: only the output's size and whether it is a witness program
+/// matter.
+pub fn dust_threshold(script_pubkey: &[u8], dust_relay_fee: FeeRate) -> Amount {
+    if is_unspendable(script_pubkey) {
+        return Amount::ZERO;
+    }
+    // Core prices the spend as a P2PKH input, with the scriptSig discounted
+    // like a witness for witness programs.
+    let spend_size = match witness_program(script_pubkey) {
+        Some(_) => 32 + 4 + 1 + 107 / WITNESS_SCALE_FACTOR as usize + 4,
+        None => 32 + 4 + 1 + 107 + 4,
+    };
+    let vbytes = output_size(script_pubkey.len()) + spend_size;
+    dust_relay_fee
+        .fee_for_vbytes(vbytes as u64)
+        .unwrap_or(Amount::MAX_MONEY)
+}
+
+/// Whether Core's relay policy rejects an output of `value` paying to
+/// `script_pubkey` as dust, using the fixed spend sizes of
+/// [`dust_threshold`]. Pass [`FeeRate::DUST_RELAY`] for Core's default.
+pub fn is_dust(value: Amount, script_pubkey: &[u8], dust_relay_fee: FeeRate) -> bool {
+    value < dust_threshold(script_pubkey, dust_relay_fee)
+}
+
+/// Length of Bitcoin's CompactSize encoding of `n`.
+pub(crate) fn compact_size_len(n: usize) -> usize {
+    match n {
+        0..0xfd => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// A script push of `len` bytes with its shortest opcode prefix.
+fn push_size(len: usize) -> usize {
+    match len {
+        0..=75 => 1 + len,
+        76..=0xff => 2 + len,
+        _ => 3 + len,
+    }
+}
+
+/// A witness stack item of `len` bytes with its length prefix.
+fn item_size(len: usize) -> usize {
+    compact_size_len(len) + len
+}
+
+/// `OP_m <n compressed keys> OP_n OP_CHECKMULTISIG`.
+fn multisig_script_size(total: u8) -> usize {
+    3 + total as usize * push_size(COMPRESSED_PUBKEY_SIZE)
+}
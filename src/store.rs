@@ -0,0 +1,479 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::compress::read_coin;
+use crate::utxo::Change;
+use crate::{BlockUndo, Error, Outpoint, Transaction, UTXO, UtxoSet, compress_coin, sha256d};
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"usnp";
+const LOG_MAGIC: &[u8; 4] = b"ulog";
+/// Magic plus the generation number.
+const LOG_HEADER_SIZE: usize = 12;
+
+const RECORD_INSERT: u8 = 0;
+const RECORD_SPEND: u8 = 1;
+const RECORD_REMOVE: u8 = 2;
+const RECORD_COMMIT: u8 = 3;
+
+/// The operations shared by every UTXO backend.
+pub trait UtxoStore {
+    fn insert(&mut self, utxo: UTXO) -> Result<(), Error>;
+    fn spend(&mut self, outpoint: &Outpoint) -> Result<UTXO, Error>;
+    fn get(&self, outpoint: &Outpoint) -> Option<&UTXO>;
+    fn apply_block(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+    ) -> Result<BlockUndo, Error>;
+    fn undo_block(&mut self, transactions: &[Transaction], undo: &BlockUndo) -> Result<(), Error>;
+}
+
+impl UtxoStore for UtxoSet {
+    fn insert(&mut self, utxo: UTXO) -> Result<(), Error> {
+        UtxoSet::insert(self, utxo)
+    }
+
+    fn spend(&mut self, outpoint: &Outpoint) -> Result<UTXO, Error> {
+        UtxoSet::spend(self, outpoint)
+    }
+
+    fn get(&self, outpoint: &Outpoint) -> Option<&UTXO> {
+        UtxoSet::get(self, outpoint)
+    }
+
+    fn apply_block(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+    ) -> Result<BlockUndo, Error> {
+        UtxoSet::apply_block(self, height, transactions)
+    }
+
+    fn undo_block(&mut self, transactions: &[Transaction], undo: &BlockUndo) -> Result<(), Error> {
+        UtxoSet::undo_block(self, transactions, undo)
+    }
+}
+
+/// A [`UtxoSet`] kept durable in a directory as a snapshot plus an
+/// append-only log of the changes made since.
+///
+/// Every call that modifies the set writes one batch of log records closed
+/// by a commit record and syncs it before returning. If that write fails,
+/// the in-memory change is undone and the log cut back, so memory never runs
+/// ahead of disk; if even that fails, the store refuses further changes
+/// until it is reopened. On open, the log is replayed up to its last intact
+/// commit; a batch torn by a crash is cut off, so the store comes back as of
+/// the last completed call. Once the log holds `snapshot_interval` batches
+/// it is folded into a fresh snapshot; as the batch that triggered it is
+/// already durable, a failure there is kept for
+/// [`take_compaction_error`](Self::take_compaction_error) rather than
+/// returned.
+pub struct FileUtxoStore {
+    dir: PathBuf,
+    set: UtxoSet,
+    tip: Option<u32>,
+    log: File,
+    /// Length of the log up to its last commit.
+    log_len: u64,
+    generation: u64,
+    batches: usize,
+    snapshot_interval: usize,
+    poisoned: bool,
+    compaction_error: Option<Error>,
+}
+
+impl FileUtxoStore {
+    pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 1000;
+
+    /// Opens the store in `dir`, creating it if needed and recovering from
+    /// any interrupted write.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (mut set, mut tip, generation) = match fs::read(dir.join("snapshot")) {
+            Ok(bytes) => read_snapshot(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (UtxoSet::new(), None, 0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let log_path = dir.join("log");
+        let mut bytes = Vec::new();
+        match File::open(&log_path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut bytes)?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let current = bytes.len() >= LOG_HEADER_SIZE
+            && &bytes[..4] == LOG_MAGIC
+            && u64::from_le_bytes(bytes[4..12].try_into().unwrap()) == generation;
+        let (batches, valid_len) = if current {
+            replay(&bytes, &mut set, &mut tip)?
+        } else {
+            // A missing, torn or stale log: a compaction may have stopped
+            // between writing the snapshot and starting the new log.
+            write_new_log(&log_path, generation)?;
+            (0, LOG_HEADER_SIZE)
+        };
+
+        let log = OpenOptions::new().read(true).write(true).open(&log_path)?;
+        // Drop a torn trailing batch so new records follow the last commit.
+        log.set_len(valid_len as u64)?;
+        log.sync_all()?;
+        Ok(FileUtxoStore {
+            dir,
+            set,
+            tip,
+            log,
+            log_len: valid_len as u64,
+            generation,
+            batches,
+            snapshot_interval: Self::DEFAULT_SNAPSHOT_INTERVAL,
+            poisoned: false,
+            compaction_error: None,
+        })
+    }
+
+    pub fn with_snapshot_interval(mut self, batches: usize) -> Self {
+        self.snapshot_interval = batches.max(1);
+        self
+    }
+
+    /// Height of the last block applied, or `None` before the first one.
+    pub fn tip_height(&self) -> Option<u32> {
+        self.tip
+    }
+
+    /// The in-memory view of the store.
+    pub fn utxos(&self) -> &UtxoSet {
+        &self.set
+    }
+
+    /// The error from the last automatic compaction, if it failed and has
+    /// not been taken yet.
+    pub fn take_compaction_error(&mut self) -> Option<Error> {
+        self.compaction_error.take()
+    }
+
+    /// Writes a snapshot of the current set and starts an empty log. A
+    /// failure before the snapshot is in place leaves the store as it was;
+    /// one after it poisons the store, as the old log no longer matches.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        if self.poisoned {
+            return Err(Error::StorePoisoned);
+        }
+        let generation = self.generation + 1;
+        let snapshot = write_snapshot(&self.set, self.tip, generation);
+        let tmp = self.dir.join("snapshot.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&snapshot)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join("snapshot"))?;
+
+        let result = self.start_log(generation);
+        if result.is_err() {
+            self.poisoned = true;
+        }
+        result
+    }
+
+    fn start_log(&mut self, generation: u64) -> Result<(), Error> {
+        sync_dir(&self.dir)?;
+        let log_path = self.dir.join("log");
+        write_new_log(&log_path, generation)?;
+        self.log = OpenOptions::new().read(true).write(true).open(&log_path)?;
+        self.log_len = LOG_HEADER_SIZE as u64;
+        self.generation = generation;
+        self.batches = 0;
+        Ok(())
+    }
+
+    /// Makes `changes`, already applied to the set, durable along with the
+    /// new `tip`, or reverts them if the log cannot be written.
+    fn commit(&mut self, changes: Vec<Change>, tip: Option<u32>) -> Result<(), Error> {
+        let mut batch = change_records(&changes);
+        let mut commit = vec![RECORD_COMMIT];
+        write_tip(&mut commit, tip);
+        write_record(&mut batch, &commit);
+
+        if let Err(e) = self.append(&batch) {
+            self.set.revert(changes);
+            return Err(e);
+        }
+        self.tip = tip;
+        self.batches += 1;
+        if self.batches >= self.snapshot_interval {
+            // The batch is durable already; a later commit tries again.
+            self.compaction_error = self.compact().err();
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, batch: &[u8]) -> Result<(), Error> {
+        if self.poisoned {
+            return Err(Error::StorePoisoned);
+        }
+        let result = (|| -> std::io::Result<()> {
+            self.log.seek(SeekFrom::Start(self.log_len))?;
+            self.log.write_all(batch)?;
+            self.log.sync_data()
+        })();
+        if let Err(e) = result {
+            // Cut off whatever part of the batch reached the file, so the
+            // next batch follows the last commit.
+            let truncated = self
+                .log
+                .set_len(self.log_len)
+                .and_then(|()| self.log.sync_data());
+            if truncated.is_err() {
+                self.poisoned = true;
+            }
+            return Err(e.into());
+        }
+        self.log_len += batch.len() as u64;
+        Ok(())
+    }
+}
+
+impl UtxoStore for FileUtxoStore {
+    fn insert(&mut self, utxo: UTXO) -> Result<(), Error> {
+        self.set.insert(utxo.clone())?;
+        self.commit(vec![Change::Added(utxo)], self.tip)
+    }
+
+    fn spend(&mut self, outpoint: &Outpoint) -> Result<UTXO, Error> {
+        let utxo = self.set.spend(outpoint)?;
+        self.commit(vec![Change::Spent(utxo.clone())], self.tip)?;
+        Ok(utxo)
+    }
+
+    fn get(&self, outpoint: &Outpoint) -> Option<&UTXO> {
+        self.set.get(outpoint)
+    }
+
+    fn apply_block(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+    ) -> Result<BlockUndo, Error> {
+        let (undo, changes) = self.set.apply_block_changes(height, transactions)?;
+        self.commit(changes, Some(height))?;
+        Ok(undo)
+    }
+
+    /// Blocks are expected to be undone in chain order, so the tip moves
+    /// back by one.
+    fn undo_block(&mut self, transactions: &[Transaction], undo: &BlockUndo) -> Result<(), Error> {
+        let changes = self.set.undo_block_changes(transactions, undo)?;
+        let tip = self.tip.and_then(|height| height.checked_sub(1));
+        self.commit(changes, tip)
+    }
+}
+
+/// Makes renames within `dir` durable.
+fn sync_dir(dir: &Path) -> Result<(), Error> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn write_new_log(path: &Path, generation: u64) -> Result<(), Error> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(LOG_MAGIC)?;
+    file.write_all(&generation.to_le_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_dir(path.parent().unwrap_or(Path::new(".")))
+}
+
+/// Replays every committed batch of `log` onto `set`, returning the number
+/// of batches and the length of the log up to the last commit.
+fn replay(log: &[u8], set: &mut UtxoSet, tip: &mut Option<u32>) -> Result<(usize, usize), Error> {
+    let mut reader = Reader::new(&log[LOG_HEADER_SIZE..]);
+    let mut pending = Vec::new();
+    let mut batches = 0;
+    let mut valid_len = LOG_HEADER_SIZE;
+    // A short or mismatched record marks where a crash cut the log off.
+    while let Some(record) = reader.record() {
+        if record.first() != Some(&RECORD_COMMIT) {
+            pending.push(record);
+            continue;
+        }
+        let mut commit = Reader::new(&record[1..]);
+        let committed_tip = commit.tip().ok_or(Error::CorruptStore)?;
+        for record in pending.drain(..) {
+            apply_record(set, record)?;
+        }
+        *tip = committed_tip;
+        batches += 1;
+        valid_len = LOG_HEADER_SIZE + reader.pos;
+    }
+    Ok((batches, valid_len))
+}
+
+fn apply_record(set: &mut UtxoSet, record: &[u8]) -> Result<(), Error> {
+    let mut reader = Reader::new(&record[1..]);
+    let applied = match record[0] {
+        RECORD_INSERT => {
+            let utxo = reader.utxo().ok_or(Error::CorruptStore)?;
+            set.insert(utxo).is_ok()
+        }
+        RECORD_SPEND => {
+            let outpoint = reader.outpoint().ok_or(Error::CorruptStore)?;
+            set.spend(&outpoint).is_ok()
+        }
+        RECORD_REMOVE => {
+            let outpoint = reader.outpoint().ok_or(Error::CorruptStore)?;
+            set.remove(&outpoint).is_some()
+        }
+        _ => false,
+    };
+    // The log only holds changes that succeeded when they were made.
+    if !applied {
+        return Err(Error::CorruptStore);
+    }
+    Ok(())
+}
+
+fn change_records(changes: &[Change]) -> Vec<u8> {
+    let mut batch = Vec::new();
+    for change in changes {
+        let record = match change {
+            Change::Added(utxo) => insert_record(utxo),
+            Change::Spent(utxo) => outpoint_record(RECORD_SPEND, &utxo.outpoint()),
+            Change::Removed(utxo) => outpoint_record(RECORD_REMOVE, &utxo.outpoint()),
+        };
+        write_record(&mut batch, &record);
+    }
+    batch
+}
+
+fn insert_record(utxo: &UTXO) -> Vec<u8> {
+    let mut record = vec![RECORD_INSERT];
+    write_utxo(&mut record, utxo);
+    record
+}
+
+fn outpoint_record(kind: u8, outpoint: &Outpoint) -> Vec<u8> {
+    let mut record = vec![kind];
+    record.extend(outpoint.to_bytes());
+    record
+}
+
+/// Frames a record as its length, the payload and a 4-byte checksum.
+fn write_record(out: &mut Vec<u8>, payload: &[u8]) {
+    out.extend((payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    out.extend(&sha256d(payload)[..4]);
+}
+
+fn write_tip(out: &mut Vec<u8>, tip: Option<u32>) {
+    match tip {
+        Some(height) => {
+            out.push(1);
+            out.extend(height.to_le_bytes());
+        }
+        None => out.push(0),
+    }
+}
+
+/// The outpoint followed by the coin in Core's compressed form.
+fn write_utxo(out: &mut Vec<u8>, utxo: &UTXO) {
+    out.extend(utxo.outpoint().to_bytes());
+    out.extend(compress_coin(utxo));
+}
+
+/// Snapshot layout: magic, generation, tip, the coins, then a checksum over
+/// all of it.
+fn write_snapshot(set: &UtxoSet, tip: Option<u32>, generation: u64) -> Vec<u8> {
+    let mut out = SNAPSHOT_MAGIC.to_vec();
+    out.extend(generation.to_le_bytes());
+    write_tip(&mut out, tip);
+    out.extend((set.len() as u64).to_le_bytes());
+    for utxo in set {
+        write_utxo(&mut out, utxo);
+    }
+    let checksum = sha256d(&out);
+    out.extend(&checksum[..4]);
+    out
+}
+
+/// Snapshots are renamed into place whole, so any damage is corruption
+/// rather than a torn write.
+fn read_snapshot(bytes: &[u8]) -> Result<(UtxoSet, Option<u32>, u64), Error> {
+    let (body, checksum) = bytes.split_last_chunk::<4>().ok_or(Error::CorruptStore)?;
+    if body.get(..4) != Some(SNAPSHOT_MAGIC) || sha256d(body)[..4] != checksum[..] {
+        return Err(Error::CorruptStore);
+    }
+    let mut reader = Reader::new(&body[4..]);
+    let mut read = || -> Option<(UtxoSet, Option<u32>, u64)> {
+        let generation = reader.u64()?;
+        let tip = reader.tip()?;
+        let mut set = UtxoSet::new();
+        for _ in 0..reader.u64()? {
+            set.insert(reader.utxo()?).ok()?;
+        }
+        Some((set, tip, generation))
+    };
+    read().ok_or(Error::CorruptStore)
+}
+
+/// Cursor over stored bytes; every read is `None` past the end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn tip(&mut self) -> Option<Option<u32>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => Some(Some(self.u32()?)),
+            _ => None,
+        }
+    }
+
+    fn outpoint(&mut self) -> Option<Outpoint> {
+        Outpoint::from_bytes(self.take(36)?).ok()
+    }
+
+    fn utxo(&mut self) -> Option<UTXO> {
+        let outpoint = self.outpoint()?;
+        let (utxo, len) = read_coin(outpoint, &self.bytes[self.pos..]).ok()?;
+        self.pos += len;
+        Some(utxo)
+    }
+
+    /// A framed log record whose checksum matches.
+    fn record(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        let payload = self.take(len)?;
+        let checksum = self.take(4)?;
+        (!payload.is_empty() && sha256d(payload)[..4] == *checksum).then_some(payload)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Amount, Outpoint, Txid, Weight, Wtxid};
+
+/// A transaction input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxIn {
+    pub previous_output: Outpoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+/// A transaction output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: Amount,
+    pub script_pubkey: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+impl Transaction {
+    /// A coinbase has a single input spending [`Outpoint::NULL`].
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.inputs.as_slice(), [input] if input.previous_output.is_null())
+    }
+
+    fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Consensus serialization, in the BIP144 format when any input has a
+    /// witness.
+    pub fn serialize(&self) -> Vec<u8> {
+        self.encode(self.has_witness())
+    }
+
+    /// The legacy serialization that txids commit to.
+    pub fn serialize_without_witness(&self) -> Vec<u8> {
+        self.encode(false)
+    }
+
+    pub fn txid(&self) -> Txid {
+        Txid::hash(&self.serialize_without_witness())
+    }
+
+    pub fn wtxid(&self) -> Wtxid {
+        Wtxid::hash(&self.serialize())
+    }
+
+    pub fn weight(&self) -> Weight {
+        Weight::from_tx_sizes(
+            self.serialize_without_witness().len() as u64,
+            self.serialize().len() as u64,
+        )
+    }
+
+    fn encode(&self, with_witness: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(self.version.to_le_bytes());
+        if with_witness {
+            // Marker and flag.
+            out.extend([0x00, 0x01]);
+        }
+        write_compact_size(&mut out, self.inputs.len() as u64);
+        for input in &self.inputs {
+            out.extend(input.previous_output.to_bytes());
+            write_bytes(&mut out, &input.script_sig);
+            out.extend(input.sequence.to_le_bytes());
+        }
+        write_compact_size(&mut out, self.outputs.len() as u64);
+        for output in &self.outputs {
+            out.extend(output.value.to_sat().to_le_bytes());
+            write_bytes(&mut out, &output.script_pubkey);
+        }
+        if with_witness {
+            for input in &self.inputs {
+                write_compact_size(&mut out, input.witness.len() as u64);
+                for item in &input.witness {
+                    write_bytes(&mut out, item);
+                }
+            }
+        }
+        out.extend(self.lock_time.to_le_bytes());
+        out
+    }
+}
+
+/// Bitcoin's CompactSize length prefix.
+pub(crate) fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..0xfd => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend((n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend((n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend(n.to_le_bytes());
+        }
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
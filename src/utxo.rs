@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use crate::transaction::write_compact_size;
+use crate::{
+    Amount, BlockHash, Error, MuHash3072, Outpoint, Transaction, UTXO, UtxoSetHash, is_unspendable,
+    sha256d,
+};
+
+/// The coins a block spent, so that it can be disconnected again. Like
+/// Core's `CBlockUndo`, it holds one entry per non-coinbase transaction with
+/// the spent coins in input order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockUndo {
+    pub spent: Vec<Vec<UTXO>>,
+}
+
+/// A single change to the set, recorded so a failed block can be reverted
+/// and so a store can persist it.
+pub(crate) enum Change {
+    Added(UTXO),
+    /// Spent by an input.
+    Spent(UTXO),
+    /// Taken out while disconnecting the block that created it.
+    Removed(UTXO),
+}
+
+/// Unspent outputs keyed by outpoint. Spent outputs are forgotten, as in
+/// Core, so spending one again is reported like any missing coin.
+/// A [`MuHash3072`] of the coins is kept up to date as they change.
+#[derive(Debug, Clone, Default)]
+pub struct UtxoSet {
+    coins: BTreeMap<Outpoint, UTXO>,
+    muhash: MuHash3072,
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a coin, failing with [`Error::DuplicateUtxo`] if its outpoint is
+    /// already unspent.
+    pub fn insert(&mut self, utxo: UTXO) -> Result<(), Error> {
+        let outpoint = utxo.outpoint();
+        if self.coins.contains_key(&outpoint) {
+            return Err(Error::DuplicateUtxo { outpoint });
+        }
+        self.add_coin(utxo);
+        Ok(())
+    }
+
+    /// Removes and returns the coin at `outpoint`.
+    pub fn spend(&mut self, outpoint: &Outpoint) -> Result<UTXO, Error> {
+        self.take_coin(outpoint).ok_or(Error::MissingUtxo {
+            outpoint: *outpoint,
+        })
+    }
+
+    /// Connects a block at `height`: spends every input and adds every
+    /// spendable output, in order, so a transaction may spend outputs of an
+    /// earlier one in the same block. Either the whole block applies or, on
+    /// the first failure, the set is left as it was.
+    pub fn apply_block(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+    ) -> Result<BlockUndo, Error> {
+        self.apply_block_changes(height, transactions)
+            .map(|(undo, _)| undo)
+    }
+
+    /// [`apply_block`](Self::apply_block), also returning what changed.
+    pub(crate) fn apply_block_changes(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+    ) -> Result<(BlockUndo, Vec<Change>), Error> {
+        let mut changes = Vec::new();
+        match self.connect(height, transactions, &mut changes) {
+            Ok(undo) => Ok((undo, changes)),
+            Err(e) => {
+                self.revert(changes);
+                Err(e)
+            }
+        }
+    }
+
+    /// Disconnects a block applied with [`apply_block`](Self::apply_block):
+    /// removes its outputs and restores the coins it spent. Atomic in the
+    /// same way.
+    pub fn undo_block(
+        &mut self,
+        transactions: &[Transaction],
+        undo: &BlockUndo,
+    ) -> Result<(), Error> {
+        self.undo_block_changes(transactions, undo).map(|_| ())
+    }
+
+    /// [`undo_block`](Self::undo_block), also returning what changed.
+    pub(crate) fn undo_block_changes(
+        &mut self,
+        transactions: &[Transaction],
+        undo: &BlockUndo,
+    ) -> Result<Vec<Change>, Error> {
+        let mut changes = Vec::new();
+        match self.disconnect(transactions, undo, &mut changes) {
+            Ok(()) => Ok(changes),
+            Err(e) => {
+                self.revert(changes);
+                Err(e)
+            }
+        }
+    }
+
+    fn connect(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+        changes: &mut Vec<Change>,
+    ) -> Result<BlockUndo, Error> {
+        let mut undo = BlockUndo::default();
+        for tx in transactions {
+            let is_coinbase = tx.is_coinbase();
+            if !is_coinbase {
+                let mut spent = Vec::with_capacity(tx.inputs.len());
+                for input in &tx.inputs {
+                    let utxo = self.spend(&input.previous_output)?;
+                    changes.push(Change::Spent(utxo.clone()));
+                    spent.push(utxo);
+                }
+                undo.spent.push(spent);
+            }
+
+            let txid = tx.txid();
+            for (vout, output) in tx.outputs.iter().enumerate() {
+                if is_unspendable(&output.script_pubkey) {
+                    continue;
+                }
+                let utxo = UTXO {
+                    txid,
+                    vout: vout as u32,
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.clone(),
+                    height,
+                    is_coinbase,
+                };
+                self.insert(utxo.clone())?;
+                changes.push(Change::Added(utxo));
+            }
+        }
+        Ok(undo)
+    }
+
+    fn disconnect(
+        &mut self,
+        transactions: &[Transaction],
+        undo: &BlockUndo,
+        changes: &mut Vec<Change>,
+    ) -> Result<(), Error> {
+        let spending = transactions.iter().filter(|tx| !tx.is_coinbase()).count();
+        if undo.spent.len() != spending {
+            return Err(Error::UndoMismatch);
+        }
+        let mut spent = undo.spent.iter().rev();
+        for tx in transactions.iter().rev() {
+            let txid = tx.txid();
+            for (vout, output) in tx.outputs.iter().enumerate().rev() {
+                if is_unspendable(&output.script_pubkey) {
+                    continue;
+                }
+                let outpoint = Outpoint(txid, vout as u32);
+                let utxo = self
+                    .remove(&outpoint)
+                    .ok_or(Error::MissingUtxo { outpoint })?;
+                changes.push(Change::Removed(utxo));
+            }
+
+            if tx.is_coinbase() {
+                continue;
+            }
+            // Checked above: one entry per non-coinbase transaction.
+            let coins = spent.next().unwrap();
+            if coins.len() != tx.inputs.len() {
+                return Err(Error::UndoMismatch);
+            }
+            for (input, utxo) in tx.inputs.iter().zip(coins).rev() {
+                if utxo.outpoint() != input.previous_output {
+                    return Err(Error::UndoMismatch);
+                }
+                self.insert(utxo.clone())?;
+                changes.push(Change::Added(utxo.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replays `changes` backwards.
+    pub(crate) fn revert(&mut self, changes: Vec<Change>) {
+        for change in changes.into_iter().rev() {
+            match change {
+                Change::Added(utxo) => {
+                    self.take_coin(&utxo.outpoint());
+                }
+                Change::Spent(utxo) | Change::Removed(utxo) => self.add_coin(utxo),
+            }
+        }
+    }
+
+    /// Takes a coin out, as disconnecting the block that created it does.
+    pub(crate) fn remove(&mut self, outpoint: &Outpoint) -> Option<UTXO> {
+        self.take_coin(outpoint)
+    }
+
+    /// Every change to `coins` goes through here and
+    /// [`take_coin`](Self::take_coin) to keep the hash in step.
+    fn add_coin(&mut self, utxo: UTXO) {
+        self.muhash.insert(&serialize_coin(&utxo));
+        self.coins.insert(utxo.outpoint(), utxo);
+    }
+
+    fn take_coin(&mut self, outpoint: &Outpoint) -> Option<UTXO> {
+        let utxo = self.coins.remove(outpoint)?;
+        self.muhash.remove(&serialize_coin(&utxo));
+        Some(utxo)
+    }
+
+    pub fn get(&self, outpoint: &Outpoint) -> Option<&UTXO> {
+        self.coins.get(outpoint)
+    }
+
+    pub fn contains(&self, outpoint: &Outpoint) -> bool {
+        self.coins.contains_key(outpoint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.coins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coins.is_empty()
+    }
+
+    /// The unspent coins in outpoint order.
+    pub fn iter(&self) -> impl Iterator<Item = &UTXO> {
+        self.coins.values()
+    }
+
+    /// The value of all unspent coins. Only a set holding more than the
+    /// coin supply could reach the cap, so it saturates there.
+    pub fn total_value(&self) -> Amount {
+        self.iter()
+            .fold(Amount::ZERO, |total, utxo| total.saturating_add(utxo.value))
+    }
+
+    /// The set's MuHash, matching `muhash` from Core's `gettxoutsetinfo`.
+    pub fn muhash(&self) -> UtxoSetHash {
+        self.muhash.finalize()
+    }
+
+    /// The double-SHA256 of `best_block` followed by every coin in outpoint
+    /// order, matching `hash_serialized_3` from `gettxoutsetinfo`. Unlike
+    /// [`muhash`](Self::muhash) it is recomputed from scratch each time.
+    pub fn hash_serialized(&self, best_block: &BlockHash) -> UtxoSetHash {
+        let mut data = best_block.to_byte_array().to_vec();
+        for utxo in self {
+            data.extend(serialize_coin(utxo));
+        }
+        UtxoSetHash::from_byte_array(sha256d(&data))
+    }
+}
+
+/// Core's `TxOutSer`, how `gettxoutsetinfo` serializes a coin for hashing:
+/// the outpoint, `height * 2 + is_coinbase` as a `u32`, then the output.
+fn serialize_coin(utxo: &UTXO) -> Vec<u8> {
+    let mut out = utxo.outpoint().to_bytes().to_vec();
+    let code = (utxo.height << 1) | u32::from(utxo.is_coinbase);
+    out.extend(code.to_le_bytes());
+    out.extend(utxo.value.to_sat().to_le_bytes());
+    write_compact_size(&mut out, utxo.script_pubkey.len() as u64);
+    out.extend_from_slice(&utxo.script_pubkey);
+    out
+}
+
+impl<'a> IntoIterator for &'a UtxoSet {
+    type Item = &'a UTXO;
+    type IntoIter = std::collections::btree_map::Values<'a, Outpoint, UTXO>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.coins.values()
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use crate::{Amount, SignedAmount, Txid, UTXO};
+
+/// A source of spendable coins. Implementors supply the data: the unspent
+/// coins, which scripts are theirs and the transaction history. Balances
+/// are derived from those, counting only coins whose script
+/// [`is_mine`](Wallet::is_mine).
+pub trait Wallet {
+    /// The wallet's unspent coins, confirmed or not.
+    fn list_unspent(&self) -> Vec<UTXO>;
+
+    /// Whether `script_pubkey` pays to the wallet.
+    fn is_mine(&self, script_pubkey: &[u8]) -> bool;
+
+    /// Transactions that touched the wallet.
+    fn history(&self) -> Vec<WalletTx>;
+
+    /// The value of every unspent coin that is the wallet's.
+    fn balance(&self) -> Amount {
+        owned(self)
+            .iter()
+            .fold(Amount::ZERO, |total, utxo| total.saturating_add(utxo.value))
+    }
+
+    /// The coins that can be spent in the block after `tip_height`: those
+    /// confirmed by then and, for coinbase outputs, mature.
+    fn spendable(&self, tip_height: u32) -> Vec<UTXO> {
+        owned(self)
+            .into_iter()
+            .filter(|utxo| utxo.confirmations(tip_height) > 0 && utxo.is_mature(tip_height))
+            .collect()
+    }
+
+    /// The balance split by spendability with the chain at `tip_height`.
+    /// Coins from blocks above the tip count as pending. A pending coin is
+    /// trusted when its transaction is [`from_me`](WalletTx::from_me) in the
+    /// history, as it cannot be double-spent by someone else.
+    fn balance_breakdown(&self, tip_height: u32) -> Balances {
+        let trusted: HashSet<Txid> = self
+            .history()
+            .into_iter()
+            .filter(|tx| tx.from_me)
+            .map(|tx| tx.txid)
+            .collect();
+        let mut balances = Balances::default();
+        for utxo in owned(self) {
+            let balance = if utxo.confirmations(tip_height) == 0 {
+                if trusted.contains(&utxo.txid) {
+                    &mut balances.trusted_pending
+                } else {
+                    &mut balances.untrusted_pending
+                }
+            } else if !utxo.is_mature(tip_height) {
+                &mut balances.immature
+            } else {
+                &mut balances.confirmed
+            };
+            *balance = balance.saturating_add(utxo.value);
+        }
+        balances
+    }
+}
+
+/// The unspent coins that pay to `wallet`.
+fn owned<W: Wallet + ?Sized>(wallet: &W) -> Vec<UTXO> {
+    wallet
+        .list_unspent()
+        .into_iter()
+        .filter(|utxo| wallet.is_mine(&utxo.script_pubkey))
+        .collect()
+}
+
+/// A wallet balance split by when the coins can be spent, like Core's
+/// `getbalances`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balances {
+    /// In a block and spendable now.
+    pub confirmed: Amount,
+    /// Not yet in a block, from transactions the wallet made itself.
+    pub trusted_pending: Amount,
+    /// Not yet in a block, from transactions others made.
+    pub untrusted_pending: Amount,
+    /// Coinbase outputs still waiting out [`COINBASE_MATURITY`].
+    ///
+    /// [`COINBASE_MATURITY`]: crate::COINBASE_MATURITY
+    pub immature: Amount,
+}
+
+impl Balances {
+    /// All the balances together, saturating at [`Amount::MAX_MONEY`].
+    pub fn total(&self) -> Amount {
+        self.confirmed
+            .saturating_add(self.trusted_pending)
+            .saturating_add(self.untrusted_pending)
+            .saturating_add(self.immature)
+    }
+}
+
+/// A transaction in a wallet's history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletTx {
+    pub txid: Txid,
+    /// Height of the block that confirmed it, or [`UTXO::MEMPOOL_HEIGHT`].
+    pub height: u32,
+    /// What the transaction added to or took from the wallet's balance.
+    pub amount: SignedAmount,
+    /// Known when the wallet funded every input.
+    pub fee: Option<Amount>,
+    /// Whether the wallet funded every input.
+    pub from_me: bool,
+}
+
+/// A wallet held entirely in memory.
+#[derive(Debug, Clone, Default)]
+pub struct TestWallet {
+    pub utxos: Vec<UTXO>,
+    pub scripts: Vec<Vec<u8>>,
+    pub history: Vec<WalletTx>,
+}
+
+impl Wallet for TestWallet {
+    fn list_unspent(&self) -> Vec<UTXO> {
+        self.utxos.clone()
+    }
+
+    fn is_mine(&self, script_pubkey: &[u8]) -> bool {
+        self.scripts.iter().any(|script| script == script_pubkey)
+    }
+
+    fn history(&self) -> Vec<WalletTx> {
+        self.history.clone()
+    }
+}
@@ -23,31 +23,398 @@ fn test_endianness_swap() {
 
 #[test]
 fn test_parse_satoshis_errors() {
-    assert_eq!(parse_satoshis("1000").unwrap(), 1000);
-    assert_eq!(parse_satoshis("abc").unwrap_err(), "Invalid satoshi amount");
+    assert_eq!(parse_satoshis("1000").unwrap().to_sat(), 1000);
+    assert_eq!(
+        parse_satoshis("2100000000000000").unwrap(),
+        Amount::MAX_MONEY
+    );
+    assert!(matches!(
+        parse_satoshis("2100000000000001"),
+        Err(Error::AmountOutOfRange {
+            sat: 2_100_000_000_000_001
+        })
+    ));
+    assert!(matches!(
+        parse_satoshis("abc"),
+        Err(Error::InvalidAmount { ref input, .. }) if input == "abc"
+    ));
+}
+
+#[test]
+fn test_hex_errors() {
+    assert!(matches!(
+        decode_hex("01zz"),
+        Err(Error::InvalidHexCharacter {
+            character: 'z',
+            offset: 2
+        })
+    ));
+    assert!(matches!(
+        hex_to_bytes("abc"),
+        Err(Error::OddHexLength { length: 3 })
+    ));
+}
+
+#[test]
+fn test_error_source_chain() {
+    use std::error::Error as _;
+
+    let err = parse_satoshis("abc").unwrap_err();
+    assert!(err.source().is_some());
+    assert_eq!(err.to_string(), "invalid satoshi amount \"abc\"");
+    // A sign is a bad digit of its own, with nothing underneath.
+    let err = parse_satoshis("-5").unwrap_err();
+    assert!(matches!(err, Error::InvalidDigit { .. }));
+    assert!(err.source().is_none());
 }
 
 #[test]
 fn test_script_classification() {
+    let p2pkh = decode_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+    assert!(matches!(classify_script(&p2pkh), ScriptType::P2PKH));
+    let p2wpkh = decode_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+    assert!(matches!(classify_script(&p2wpkh), ScriptType::P2WPKH));
     assert!(matches!(
         classify_script(&[0x76, 0xa9, 0x14]),
-        ScriptType::P2PKH
+        ScriptType::Unknown
+    ));
+    assert!(matches!(
+        classify_script(&[0xab, 0xcd]),
+        ScriptType::Unknown
+    ));
+}
+
+#[test]
+fn test_script_classification_mainnet() {
+    let cases = [
+        // Genesis block coinbase output
+        (
+            "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+            ScriptType::P2PK,
+        ),
+        (
+            "2102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5ac",
+            ScriptType::P2PK,
+        ),
+        (
+            "a914748284390f9e263a4b766a75d0633c50426eb87587",
+            ScriptType::P2SH,
+        ),
+        (
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ScriptType::P2WSH,
+        ),
+        (
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ScriptType::P2TR,
+        ),
+        ("51024e73", ScriptType::P2A),
+        ("6a0b68656c6c6f20776f726c64", ScriptType::NullData),
+        ("6a", ScriptType::NullData),
+        (
+            "5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae",
+            ScriptType::Multisig,
+        ),
+        (
+            "5210751e76e8199196d454941c45d1b3a323",
+            ScriptType::WitnessUnknown,
+        ),
+        // Version 0 programs must be 20 or 32 bytes
+        ("0010751e76e8199196d454941c45d1b3a323", ScriptType::Unknown),
+        // Multisig with m > n
+        (
+            "5221022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e51ae",
+            ScriptType::Unknown,
+        ),
+        ("6a76", ScriptType::Unknown),
+        (
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f18",
+            ScriptType::Unknown,
+        ),
+        ("", ScriptType::Unknown),
+    ];
+    for (hex, expected) in cases {
+        let script = decode_hex(hex).unwrap();
+        assert_eq!(classify_script(&script), expected, "{hex}");
+    }
+}
+
+#[test]
+fn test_decode_script_pubkey_payload() {
+    let p2pkh = decode_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+    match decode_script_pubkey(&p2pkh) {
+        ScriptPubKey::P2PKH { pubkey_hash } => {
+            assert_eq!(
+                bytes_to_hex(pubkey_hash),
+                "62e907b15cbf27d5425399ebf6f0fb50ebb88f18"
+            )
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    let p2tr =
+        decode_hex("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+    assert!(matches!(
+        decode_script_pubkey(&p2tr),
+        ScriptPubKey::P2TR { output_key } if output_key[0] == 0x79
+    ));
+
+    let multisig = decode_hex("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae").unwrap();
+    match decode_script_pubkey(&multisig) {
+        ScriptPubKey::Multisig { required, pubkeys } => {
+            assert_eq!(required, 1);
+            assert_eq!(pubkeys.len(), 2);
+            assert!(pubkeys.iter().all(|key| key.len() == 33));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn test_script_pubkey_round_trip() {
+    for hex in [
+        "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+        "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+        "a914748284390f9e263a4b766a75d0633c50426eb87587",
+        "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+        "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "51024e73",
+        "6a4c0b68656c6c6f20776f726c64",
+        "5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae",
+        "5210751e76e8199196d454941c45d1b3a323",
+        "abcd",
+    ] {
+        let script = decode_hex(hex).unwrap();
+        assert_eq!(decode_script_pubkey(&script).to_script(), script, "{hex}");
+    }
+}
+
+#[test]
+fn test_script_num_encoding() {
+    for (value, hex) in [
+        (0, ""),
+        (1, "01"),
+        (-1, "81"),
+        (127, "7f"),
+        (128, "8000"),
+        (-128, "8080"),
+        (255, "ff00"),
+        (-255, "ff80"),
+        (0x7fff_ffff, "ffffff7f"),
+    ] {
+        assert_eq!(bytes_to_hex(&encode_script_num(value)), hex);
+        assert_eq!(
+            decode_script_num(&decode_hex(hex).unwrap(), true, 4).unwrap(),
+            value
+        );
+    }
+    // Negative zero and padded encodings decode unless minimality is required.
+    assert_eq!(decode_script_num(&[0x80], false, 4).unwrap(), 0);
+    assert_eq!(decode_script_num(&[0x01, 0x00], false, 4).unwrap(), 1);
+    assert_eq!(
+        decode_script_num(&[0x01, 0x00], true, 4),
+        Err(ScriptError::InvalidNumber)
+    );
+    // Too long for the caller, or for an i64, is an error rather than a panic.
+    assert_eq!(
+        decode_script_num(&[1; 5], false, 4),
+        Err(ScriptError::InvalidNumber)
+    );
+    assert_eq!(
+        decode_script_num(&[1; 5], false, 5).unwrap(),
+        0x01_0101_0101
+    );
+    assert_eq!(
+        decode_script_num(&[1; 9], false, usize::MAX),
+        Err(ScriptError::InvalidNumber)
+    );
+    assert_eq!(decode_script_num(&[0xff; 8], false, 8).unwrap(), -i64::MAX);
+}
+
+#[test]
+fn test_disassemble() {
+    let p2pkh = decode_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+    assert_eq!(
+        disassemble(&p2pkh),
+        "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"
+    );
+    assert_eq!(
+        disassemble(&decode_hex("00144f51b10360ac4f60609f1a69e49f00f5e5e0ce11").unwrap()),
+        "0 4f51b10360ac4f60609f1a69e49f00f5e5e0ce11"
+    );
+    assert_eq!(
+        disassemble(&[0x4f, 0x51, 0x60, 0x02, 0xe8, 0x03, 0xb1, 0x50, 0xbb]),
+        "-1 1 16 1000 OP_CHECKLOCKTIMEVERIFY OP_RESERVED OP_UNKNOWN"
+    );
+    assert_eq!(disassemble(&[0x76, 0x05, 0x01]), "OP_DUP [error]");
+    assert_eq!(disassemble(&[]), "");
+}
+
+#[test]
+fn test_disassemble_sighash() {
+    let mut sig = vec![0x30, 0x44, 0x02, 0x20];
+    sig.extend([0x11; 32]);
+    sig.extend([0x02, 0x20]);
+    sig.extend([0x22; 32]);
+    let der = bytes_to_hex(&sig);
+
+    let mut script_sig = vec![0x47];
+    script_sig.extend(&sig);
+    script_sig.push(0x81);
+    assert_eq!(disassemble(&script_sig), format!("{der}[ALL|ANYONECANPAY]"));
+
+    // Undefined sighash types are left as plain hex.
+    *script_sig.last_mut().unwrap() = 0x04;
+    assert_eq!(disassemble(&script_sig), format!("{der}04"));
+
+    // Unspendable scripts are never decoded.
+    script_sig.insert(0, 0x6a);
+    *script_sig.last_mut().unwrap() = 0x01;
+    assert_eq!(disassemble(&script_sig), format!("OP_RETURN {der}01"));
+}
+
+#[test]
+fn test_assemble() {
+    assert_eq!(
+        bytes_to_hex(
+            &assemble(
+                "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"
+            )
+            .unwrap()
+        ),
+        "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"
+    );
+    assert_eq!(
+        assemble("0 -1 1 16 17 -128 1000 NOP2").unwrap(),
+        [
+            0x00, 0x4f, 0x51, 0x60, 0x01, 0x11, 0x02, 0x80, 0x80, 0x02, 0xe8, 0x03, 0xb1
+        ]
+    );
+    assert_eq!(
+        assemble("0x4c 0x01 0x07 'ab'").unwrap(),
+        [0x4c, 0x01, 0x07, 0x02, b'a', b'b']
+    );
+    assert!(matches!(
+        assemble("OP_DUP OP_BOGUS"),
+        Err(Error::InvalidAsmToken { ref token, offset: 7 }) if token == "OP_BOGUS"
+    ));
+    assert!(matches!(
+        assemble("4294967296"),
+        Err(Error::InvalidAsmToken { .. })
+    ));
+}
+
+#[test]
+fn test_assemble_round_trip() {
+    let mut sig = vec![0x47, 0x30, 0x44, 0x02, 0x20];
+    sig.extend([0x11; 32]);
+    sig.extend([0x02, 0x20]);
+    sig.extend([0x22; 32]);
+    sig.push(0x03);
+    for script in [
+        decode_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap(),
+        decode_hex("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae").unwrap(),
+        decode_hex("6a0b68656c6c6f20776f726c64").unwrap(),
+        sig,
+        decode_hex("05010203040a").unwrap(),
+    ] {
+        assert_eq!(assemble(&disassemble(&script)).unwrap(), script);
+    }
+
+    // Pushes whose hex is all digits read back as numbers.
+    let digits = decode_hex("050102030405").unwrap();
+    assert_eq!(disassemble(&digits), "0102030405");
+    assert_eq!(
+        bytes_to_hex(&assemble(&disassemble(&digits)).unwrap()),
+        "0445dc1406"
+    );
+    let too_big = decode_hex("059999999999").unwrap();
+    assert!(matches!(
+        assemble(&disassemble(&too_big)),
+        Err(Error::InvalidAsmToken { .. })
     ));
     assert!(matches!(
-        classify_script(&[0x00, 0x14, 0xff]),
+        assemble("OP_DUP 0x"),
+        Err(Error::InvalidAsmToken { offset: 7, .. })
+    ));
+}
+
+#[test]
+fn test_script_builder_push_encoding() {
+    let script = ScriptBuilder::new()
+        .push_slice(&[])
+        .push_slice(&[0x05])
+        .push_slice(&[0x81])
+        .push_slice(&[0x11])
+        .push_slice(&[0xaa; 80])
+        .push_int(-1)
+        .push_int(1000)
+        .into_script();
+    assert_eq!(
+        disassemble(&script),
+        format!("0 5 -1 17 {} -1 1000", "aa".repeat(80))
+    );
+    assert_eq!(&script[..6], [0x00, 0x55, 0x4f, 0x01, 0x11, 0x4c]);
+
+    let big = ScriptBuilder::new().push_slice(&[0u8; 300]).into_script();
+    assert_eq!(&big[..3], [0x4d, 0x2c, 0x01]);
+}
+
+#[test]
+fn test_script_builder_templates() {
+    let hash20 = [0x62; 20];
+    let hash32 = [0x18; 32];
+    let key =
+        decode_hex("022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e").unwrap();
+
+    let p2pkh = ScriptBuilder::p2pkh(&hash20);
+    assert_eq!(p2pkh.len(), 25);
+    assert_eq!(classify_script(&p2pkh), ScriptType::P2PKH);
+    assert_eq!(
+        classify_script(&ScriptBuilder::p2sh(&hash20)),
+        ScriptType::P2SH
+    );
+    assert_eq!(
+        classify_script(&ScriptBuilder::p2wpkh(&hash20)),
         ScriptType::P2WPKH
+    );
+    assert_eq!(
+        classify_script(&ScriptBuilder::p2wsh(&hash32)),
+        ScriptType::P2WSH
+    );
+    assert_eq!(
+        classify_script(&ScriptBuilder::p2tr(&hash32)),
+        ScriptType::P2TR
+    );
+    assert_eq!(
+        classify_script(&ScriptBuilder::null_data(b"hello")),
+        ScriptType::NullData
+    );
+    assert_eq!(
+        classify_script(&ScriptBuilder::multisig(1, &[&key, &key]).unwrap()),
+        ScriptType::Multisig
+    );
+
+    assert!(matches!(
+        ScriptBuilder::multisig(3, &[&key, &key]),
+        Err(Error::InvalidMultisig {
+            required: 3,
+            count: 2
+        })
     ));
     assert!(matches!(
-        classify_script(&[0xab, 0xcd]),
-        ScriptType::Unknown
+        ScriptBuilder::multisig(1, &[&key, &key[1..]]),
+        Err(Error::InvalidPubkey { index: 1 })
     ));
 }
 
 #[test]
 fn test_outpoint_destructuring() {
-    let op = Outpoint("abcd1234".to_string(), 1);
-    let Outpoint(txid, vout) = op;
-    assert_eq!(txid, "abcd1234");
+    let txid = Txid::from_byte_array([0xab; 32]);
+    let op = Outpoint(txid, 1);
+    let Outpoint(spent, vout) = op;
+    assert_eq!(spent, txid);
     assert_eq!(vout, 1);
 }
 
@@ -57,44 +424,1664 @@ fn test_script_slice() {
     script.extend(vec![0u8; 20]);
     let data = read_pushdata(&script);
     assert_eq!(data.len(), 20);
+
+    assert_eq!(
+        read_pushdata(&[0x4c, 0x03, 0xaa, 0xbb, 0xcc]),
+        [0xaa, 0xbb, 0xcc]
+    );
+    assert!(read_pushdata(&[0x14, 0x00]).is_empty());
+}
+
+#[test]
+fn test_instruction_iterator() {
+    let mut script = vec![0x00, 0x02, 0xaa, 0xbb, 0x76];
+    script.extend([0x4c, 0x01, 0x11]);
+    script.extend([0x4d, 0x02, 0x00, 0x22, 0x33]);
+    script.extend([0x4e, 0x01, 0x00, 0x00, 0x00, 0x44]);
+    let parsed: Vec<_> = instructions(&script).collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        parsed,
+        vec![
+            Instruction::Push(&[]),
+            Instruction::Push(&[0xaa, 0xbb]),
+            Instruction::Op(Opcode::OpDup),
+            Instruction::Push(&[0x11]),
+            Instruction::Push(&[0x22, 0x33]),
+            Instruction::Push(&[0x44]),
+        ]
+    );
+}
+
+#[test]
+fn test_instruction_iterator_errors() {
+    let mut iter = instructions(&[0x76, 0x05, 0x01, 0x02]);
+    assert_eq!(
+        iter.next().unwrap().unwrap(),
+        Instruction::Op(Opcode::OpDup)
+    );
+    assert!(matches!(
+        iter.next(),
+        Some(Err(Error::TruncatedPush {
+            offset: 1,
+            expected: 5,
+            available: 2
+        }))
+    ));
+    assert!(iter.next().is_none());
+
+    assert!(matches!(
+        instructions(&[0x4d, 0x01]).next(),
+        Some(Err(Error::TruncatedPush {
+            offset: 0,
+            expected: 2,
+            available: 1
+        }))
+    ));
+
+    let mut iter = instructions(&[0xbb, 0x76]);
+    assert!(matches!(
+        iter.next(),
+        Some(Err(Error::InvalidOpcode { byte: 0xbb }))
+    ));
+    assert_eq!(iter.position(), 1);
+    assert_eq!(
+        iter.next().unwrap().unwrap(),
+        Instruction::Op(Opcode::OpDup)
+    );
 }
 
 #[test]
 fn test_wallet_balance_trait() {
-    let wallet = TestWallet { confirmed: 1500 };
-    assert_eq!(wallet.balance(), 1500);
+    let wallet = TestWallet {
+        utxos: vec![UTXO {
+            txid: Txid::all_zeros(),
+            vout: 0,
+            value: Amount::from_sat(1500).unwrap(),
+            script_pubkey: vec![0x51],
+            height: 1,
+            is_coinbase: false,
+        }],
+        scripts: vec![vec![0x51]],
+        ..TestWallet::default()
+    };
+    assert_eq!(wallet.balance().to_sat(), 1500);
 }
 
 #[test]
 fn test_apply_fee() {
-    let mut balance = 10000;
-    apply_fee(&mut balance, 250);
-    assert_eq!(balance, 9750);
+    let mut balance = Amount::from_sat(10000).unwrap();
+    apply_fee(&mut balance, Amount::from_sat(250).unwrap()).unwrap();
+    assert_eq!(balance.to_sat(), 9750);
+
+    let fee = Amount::from_sat(10000).unwrap();
+    assert!(matches!(
+        apply_fee(&mut balance, fee),
+        Err(Error::InsufficientFunds { required, .. }) if required == fee
+    ));
+    assert_eq!(balance.to_sat(), 9750);
+}
+
+#[test]
+fn test_amount_arithmetic() {
+    let sat = |n| Amount::from_sat(n).unwrap();
+    assert_eq!(Amount::MAX_MONEY.to_sat(), 2_100_000_000_000_000);
+    assert!(Amount::from_sat(Amount::MAX_MONEY.to_sat() + 1).is_err());
+    assert!(Amount::try_from(u64::MAX).is_err());
+
+    assert_eq!(sat(5).checked_add(sat(7)), Some(sat(12)));
+    assert_eq!(Amount::MAX_MONEY.checked_add(Amount::ONE_SAT), None);
+    assert_eq!(
+        Amount::MAX_MONEY.saturating_add(Amount::ONE_BTC),
+        Amount::MAX_MONEY
+    );
+    assert_eq!(sat(5).checked_sub(sat(7)), None);
+    assert_eq!(sat(5).saturating_sub(sat(7)), Amount::ZERO);
+    assert_eq!(sat(5) - sat(2), sat(3));
+    assert_eq!(Amount::ONE_BTC.checked_mul(21_000_001), None);
+    assert_eq!(sat(7).checked_div(2), Some(sat(3)));
+    assert_eq!(sat(7).checked_div(0), None);
+
+    let values = [sat(1), sat(2), sat(3)];
+    assert_eq!(values.iter().sum::<Amount>(), sat(6));
+    assert_eq!(values.into_iter().sum::<Amount>(), sat(6));
+    assert_eq!(Amount::checked_sum(values), Some(sat(6)));
+    assert_eq!(
+        Amount::checked_sum([Amount::MAX_MONEY, Amount::ONE_SAT]),
+        None
+    );
+    assert_eq!(u64::from(sat(42)), 42);
+    assert_eq!(sat(42).to_string(), "42 sat");
 }
 
 #[test]
 fn test_move_txid() {
-    let original = "deadbeef".to_string();
+    let hex = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+    let original: Txid = hex.parse().unwrap();
     let result = move_txid(original);
-    assert_eq!(result, "txid: deadbeef");
+    assert_eq!(result, format!("txid: {hex}"));
 }
 
 #[test]
 fn test_opcode_parsing() {
-    assert_eq!(Opcode::from_byte(0xac), Ok(Opcode::OpChecksig));
-    assert_eq!(Opcode::from_byte(0x76), Ok(Opcode::OpDup));
-    assert_eq!(
-        Opcode::from_byte(0x00),
-        Err("Invalid opcode: 0x00".to_string())
-    );
+    assert_eq!(Opcode::from_byte(0xac).unwrap(), Opcode::OpChecksig);
+    assert_eq!(Opcode::from_byte(0x76).unwrap(), Opcode::OpDup);
+    assert!(matches!(
+        Opcode::from_byte(0xfe),
+        Err(Error::InvalidOpcode { byte: 0xfe })
+    ));
 }
 
 #[test]
 fn test_utxo_ownership() {
     let utxo = UTXO {
-        txid: vec![0xaa, 0xbb],
+        txid: Txid::from_byte_array([0xaa; 32]),
         vout: 0,
-        value: 1000,
+        value: Amount::from_sat(1000).unwrap(),
+        script_pubkey: ScriptBuilder::p2wpkh(&[0x11; 20]),
+        height: 0,
+        is_coinbase: false,
     };
     assert_eq!(consume_utxo(utxo.clone()), utxo);
 }
+
+#[test]
+fn test_opcode_table_round_trip() {
+    for byte in 0..=u8::MAX {
+        match Opcode::from_byte(byte) {
+            Ok(op) => {
+                assert_eq!(op.to_byte(), byte);
+                assert_eq!(op.to_string().parse::<Opcode>().unwrap(), op);
+            }
+            Err(Error::InvalidOpcode { byte: b }) => {
+                assert_eq!(b, byte);
+                assert!((0xbb..=0xfe).contains(&byte));
+            }
+            Err(e) => panic!("unexpected error {e}"),
+        }
+    }
+    assert_eq!(Opcode::from_byte(0x14).unwrap(), Opcode::OpPushBytes(20));
+    assert_eq!(
+        Opcode::OpChecklocktimeverify.to_string(),
+        "OP_CHECKLOCKTIMEVERIFY"
+    );
+    assert_eq!(
+        "OP_NOP2".parse::<Opcode>().unwrap(),
+        Opcode::OpChecklocktimeverify
+    );
+    assert!(matches!(
+        "OP_BOGUS".parse::<Opcode>(),
+        Err(Error::InvalidOpcodeName { .. })
+    ));
+}
+
+#[test]
+fn test_opcode_classification() {
+    assert!(Opcode::Op0.is_push());
+    assert!(Opcode::OpPushdata2.is_push());
+    assert!(Opcode::Op16.is_push());
+    assert!(!Opcode::OpReserved.is_push());
+    assert!(!Opcode::OpDup.is_push());
+
+    assert!(Opcode::OpCat.is_disabled());
+    assert!(!Opcode::OpAdd.is_disabled());
+
+    assert!(Opcode::OpNotif.is_conditional());
+    assert!(!Opcode::OpVerify.is_conditional());
+
+    assert!(Opcode::OpCat.is_success());
+    assert!(!Opcode::OpChecksigadd.is_success());
+    assert!(Opcode::is_success_byte(0xbb));
+    assert!(!Opcode::is_success_byte(0xff));
+}
+
+fn parse_verify_flags(flags: &str) -> VerifyFlags {
+    flags
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .fold(VerifyFlags::NONE, |acc, name| {
+            acc | match name {
+                "NONE" => VerifyFlags::NONE,
+                "P2SH" => VerifyFlags::P2SH,
+                "STRICTENC" => VerifyFlags::STRICTENC,
+                "DERSIG" => VerifyFlags::DERSIG,
+                "LOW_S" => VerifyFlags::LOW_S,
+                "NULLDUMMY" => VerifyFlags::NULLDUMMY,
+                "SIGPUSHONLY" => VerifyFlags::SIGPUSHONLY,
+                "MINIMALDATA" => VerifyFlags::MINIMALDATA,
+                "DISCOURAGE_UPGRADABLE_NOPS" => VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS,
+                "CLEANSTACK" => VerifyFlags::CLEANSTACK,
+                "CHECKLOCKTIMEVERIFY" => VerifyFlags::CHECKLOCKTIMEVERIFY,
+                "CHECKSEQUENCEVERIFY" => VerifyFlags::CHECKSEQUENCEVERIFY,
+                "NULLFAIL" => VerifyFlags::NULLFAIL,
+                other => panic!("unknown flag {other}"),
+            }
+        })
+}
+
+fn core_error_name(err: ScriptError) -> &'static str {
+    match err {
+        ScriptError::EvalFalse => "EVAL_FALSE",
+        ScriptError::OpReturn => "OP_RETURN",
+        ScriptError::ScriptSize => "SCRIPT_SIZE",
+        ScriptError::PushSize => "PUSH_SIZE",
+        ScriptError::OpCount => "OP_COUNT",
+        ScriptError::StackSize => "STACK_SIZE",
+        ScriptError::SigCount => "SIG_COUNT",
+        ScriptError::PubkeyCount => "PUBKEY_COUNT",
+        ScriptError::Verify => "VERIFY",
+        ScriptError::EqualVerify => "EQUALVERIFY",
+        ScriptError::CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+        ScriptError::CheckSigVerify => "CHECKSIGVERIFY",
+        ScriptError::NumEqualVerify => "NUMEQUALVERIFY",
+        ScriptError::BadOpcode => "BAD_OPCODE",
+        ScriptError::DisabledOpcode => "DISABLED_OPCODE",
+        ScriptError::InvalidStackOperation => "INVALID_STACK_OPERATION",
+        ScriptError::InvalidAltstackOperation => "INVALID_ALTSTACK_OPERATION",
+        ScriptError::UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+        ScriptError::NegativeLocktime => "NEGATIVE_LOCKTIME",
+        ScriptError::UnsatisfiedLocktime => "UNSATISFIED_LOCKTIME",
+        ScriptError::SigHashtype => "SIG_HASHTYPE",
+        ScriptError::SigDer => "SIG_DER",
+        ScriptError::MinimalData => "MINIMALDATA",
+        ScriptError::SigPushOnly => "SIG_PUSHONLY",
+        ScriptError::SigHighS => "SIG_HIGH_S",
+        ScriptError::SigNullDummy => "SIG_NULLDUMMY",
+        ScriptError::PubkeyType => "PUBKEYTYPE",
+        ScriptError::CleanStack => "CLEANSTACK",
+        ScriptError::SigNullFail => "NULLFAIL",
+        ScriptError::DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+        // Core reports out-of-range and non-minimal numbers as UNKNOWN_ERROR.
+        ScriptError::InvalidNumber => "UNKNOWN_ERROR",
+    }
+}
+
+#[test]
+fn test_core_script_vectors() {
+    let vectors: Vec<serde_json::Value> =
+        serde_json::from_str(include_str!("script_tests.json")).unwrap();
+    let mut checked = 0;
+    for vector in &vectors {
+        let row = vector.as_array().unwrap();
+        // Single-element rows are comments; witness vectors lead with an array.
+        if row.len() < 4 || row[0].is_array() {
+            continue;
+        }
+        let script_sig = assemble(row[0].as_str().unwrap()).unwrap();
+        let script_pubkey = assemble(row[1].as_str().unwrap()).unwrap();
+        let flags = parse_verify_flags(row[2].as_str().unwrap());
+        let expected = row[3].as_str().unwrap();
+
+        let result = verify_script(&script_sig, &script_pubkey, flags, &NoSignatureChecker);
+        let actual = match result {
+            Ok(()) => "OK",
+            Err(Error::Script(err)) => core_error_name(err),
+            Err(e) => panic!("{vector}: unexpected error {e}"),
+        };
+        assert_eq!(actual, expected, "{vector}");
+        checked += 1;
+    }
+    assert!(checked > 500);
+}
+
+struct AcceptKey(Vec<u8>);
+
+impl SignatureChecker for AcceptKey {
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], _script_code: &[u8]) -> bool {
+        !sig.is_empty() && pubkey == self.0.as_slice()
+    }
+}
+
+#[test]
+fn test_eval_script_limits_and_checker() {
+    let mut stack = Vec::new();
+    eval_script(
+        &mut stack,
+        &assemble("1 2 ADD").unwrap(),
+        VerifyFlags::NONE,
+        &NoSignatureChecker,
+    )
+    .unwrap();
+    assert_eq!(stack, vec![vec![3]]);
+    assert!(cast_to_bool(&stack[0]));
+    assert!(!cast_to_bool(&[0x00, 0x80]));
+
+    let oversized = vec![0x61; MAX_SCRIPT_SIZE + 1];
+    assert!(matches!(
+        eval_script(
+            &mut Vec::new(),
+            &oversized,
+            VerifyFlags::NONE,
+            &NoSignatureChecker
+        ),
+        Err(Error::Script(ScriptError::ScriptSize))
+    ));
+
+    let pubkey = [0x02; 33];
+    let checker = AcceptKey(pubkey.to_vec());
+    let script_pubkey = ScriptBuilder::new()
+        .push_slice(&pubkey)
+        .push_opcode(Opcode::OpChecksig)
+        .into_script();
+    let script_sig = ScriptBuilder::new().push_slice(&[0x30, 0x01]).into_script();
+    verify_script(&script_sig, &script_pubkey, VerifyFlags::NONE, &checker).unwrap();
+    assert!(matches!(
+        verify_script(&[0x00], &script_pubkey, VerifyFlags::NONE, &checker),
+        Err(Error::Script(ScriptError::EvalFalse))
+    ));
+}
+
+#[test]
+fn test_trace_script_steps() {
+    let script = assemble("1 IF 2 ELSE 3 ENDIF DUP ADD").unwrap();
+    let mut stack = Vec::new();
+    let trace = trace_script(&mut stack, &script, VerifyFlags::NONE, &NoSignatureChecker);
+    assert_eq!(trace.result, Ok(()));
+    assert_eq!(stack, vec![vec![4]]);
+    assert!(trace.failed_step().is_none());
+
+    let asm: Vec<&str> = trace.steps.iter().map(|s| s.asm.as_str()).collect();
+    assert_eq!(
+        asm,
+        [
+            "1", "OP_IF", "2", "OP_ELSE", "3", "OP_ENDIF", "OP_DUP", "OP_ADD"
+        ]
+    );
+    let executed: Vec<bool> = trace.steps.iter().map(|s| s.executed).collect();
+    assert_eq!(executed, [true, true, true, true, false, false, true, true]);
+    assert_eq!(trace.steps[1].branch, [true]);
+    assert_eq!(trace.steps[3].branch, [false]);
+    assert!(trace.steps[5].branch.is_empty());
+    assert_eq!(trace.steps[7].opcode, Some(Opcode::OpAdd));
+    assert_eq!(trace.steps[7].stack_before, vec![vec![2], vec![2]]);
+    assert_eq!(trace.steps[7].stack_after, vec![vec![4]]);
+
+    let mut debugger = Debugger::new(Vec::new(), &script, VerifyFlags::NONE, &NoSignatureChecker);
+    assert_eq!(debugger.step().unwrap().stack_after, vec![vec![1]]);
+    assert_eq!(debugger.stack(), [vec![1]]);
+    assert!(debugger.result().is_none());
+    let (trace, _) = debugger.finish();
+    assert_eq!(trace.steps.len(), 8);
+}
+
+#[test]
+fn test_trace_verify_script_failure() {
+    let pubkey_hash = hash160(&[0x02; 33]);
+    let script_pubkey = ScriptBuilder::p2pkh(&pubkey_hash);
+    let script_sig = ScriptBuilder::new()
+        .push_slice(&[0x30, 0x01])
+        .push_slice(&[0x03; 33])
+        .into_script();
+    let (result, traces) = trace_verify_script(
+        &script_sig,
+        &script_pubkey,
+        VerifyFlags::P2SH,
+        &NoSignatureChecker,
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Script(ScriptError::EqualVerify))
+    ));
+    assert_eq!(traces.len(), 2);
+    assert_eq!(traces[0].result, Ok(()));
+    let failed = traces[1].failed_step().unwrap();
+    assert_eq!(failed.opcode, Some(Opcode::OpEqualverify));
+    assert_eq!(failed.offset, 23);
+    assert_eq!(failed.error, Some(ScriptError::EqualVerify));
+
+    let table = traces[1].to_table();
+    assert!(table.starts_with("#  offset  instruction"));
+    assert!(table.contains("OP_EQUALVERIFY"));
+    assert!(table.ends_with("result: script failed an OP_EQUALVERIFY operation\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&traces[1].to_json()).unwrap();
+    assert_eq!(json["script"], bytes_to_hex(&script_pubkey));
+    assert_eq!(json["steps"].as_array().unwrap().len(), 4);
+    assert_eq!(json["steps"][3]["opcode"], "OP_EQUALVERIFY");
+    assert_eq!(
+        json["steps"][1]["stack_after"][2],
+        bytes_to_hex(&hash160(&[0x03; 33]))
+    );
+    assert_eq!(json["steps"][3]["error"], json["error"]);
+}
+
+#[test]
+fn test_parse_denominations() {
+    let sat = |s: &str| parse_satoshis(s).unwrap().to_sat();
+    assert_eq!(sat("0.0015 BTC"), 150_000);
+    assert_eq!(sat("150 mBTC"), 15_000_000);
+    assert_eq!(sat("2,500 sat"), 2_500);
+    assert_eq!(sat("1.5 bits"), 150);
+    assert_eq!(sat("1,000,000 sats"), 1_000_000);
+    assert_eq!(sat("21000000 BTC"), 2_100_000_000_000_000);
+    assert_eq!(sat("0.00000001BTC"), 1);
+    assert_eq!(sat(".5 mBTC"), 50_000);
+    assert_eq!(sat("1. BTC"), 100_000_000);
+    assert_eq!(sat("2.50 uBTC"), 250);
+    assert_eq!(sat("1.000 sat"), 1);
+    assert_eq!(sat("  42 "), 42);
+
+    assert!(matches!(
+        parse_satoshis("0.000000015 BTC"),
+        Err(Error::TooPrecise {
+            denomination: Denomination::Bitcoin,
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse_satoshis("1.5 sat"),
+        Err(Error::TooPrecise { ref input, .. }) if input == "1.5 sat"
+    ));
+    assert!(matches!(
+        parse_satoshis("5 MBTC"),
+        Err(Error::UnknownDenomination { ref unit }) if unit == "MBTC"
+    ));
+    assert!(matches!(
+        parse_satoshis("25,00 sat"),
+        Err(Error::InvalidAmount { .. })
+    ));
+    assert!(matches!(
+        parse_satoshis("1.2.3 BTC"),
+        Err(Error::InvalidDigit { .. })
+    ));
+    assert!(matches!(
+        parse_satoshis("1.+5"),
+        Err(Error::InvalidDigit { ref input }) if input == "1.+5"
+    ));
+    assert!(matches!(
+        parse_satoshis("."),
+        Err(Error::InvalidDigit { .. })
+    ));
+    for signed in ["+5", "-5", "-0", "- 5", "-0.5 BTC"] {
+        assert!(
+            matches!(parse_satoshis(signed), Err(Error::InvalidDigit { .. })),
+            "{signed}"
+        );
+    }
+    assert!(matches!(
+        Amount::from_str_in("1.-5", Denomination::Bitcoin),
+        Err(Error::InvalidDigit { .. })
+    ));
+    assert_eq!(sat(".5 BTC"), 50_000_000);
+    assert_eq!(sat("5. BTC"), 500_000_000);
+    assert!(matches!(
+        parse_satoshis("21000000.00000001 BTC"),
+        Err(Error::AmountOutOfRange { .. })
+    ));
+    assert!(matches!(
+        parse_satoshis("99999999999999999999 sat"),
+        Err(Error::AmountOutOfRange { .. })
+    ));
+}
+
+#[test]
+fn test_format_denominations() {
+    let amount = Amount::from_sat(150_000).unwrap();
+    assert_eq!(amount.to_string_in(Denomination::Bitcoin), "0.0015 BTC");
+    assert_eq!(amount.to_string_in(Denomination::MilliBitcoin), "1.5 mBTC");
+    assert_eq!(amount.to_string_in(Denomination::MicroBitcoin), "1500 bits");
+    assert_eq!(amount.to_string_in(Denomination::Satoshi), "150000 sat");
+    assert_eq!(Amount::ZERO.to_string_in(Denomination::Bitcoin), "0 BTC");
+    assert_eq!(
+        Amount::MAX_MONEY.to_string_in(Denomination::Bitcoin),
+        "21000000 BTC"
+    );
+
+    for denomination in [
+        Denomination::Bitcoin,
+        Denomination::MilliBitcoin,
+        Denomination::MicroBitcoin,
+        Denomination::Satoshi,
+    ] {
+        for sat in [0, 1, 99, 123_456_789, 2_100_000_000_000_000] {
+            let amount = Amount::from_sat(sat).unwrap();
+            let text = amount.to_string_in(denomination);
+            assert_eq!(parse_satoshis(&text).unwrap(), amount, "{text}");
+        }
+    }
+}
+
+#[test]
+fn test_signed_amount() {
+    let signed = |n| SignedAmount::from_sat(n).unwrap();
+    let sat = |n| Amount::from_sat(n).unwrap();
+
+    assert_eq!(sat(1000).signed_sub(sat(1250)), signed(-250));
+    assert_eq!(SignedAmount::from(sat(5)), signed(5));
+    assert_eq!(Amount::try_from(signed(5)).unwrap(), sat(5));
+    assert!(matches!(
+        signed(-5).to_unsigned(),
+        Err(Error::NegativeAmount { sat: -5 })
+    ));
+    assert_eq!(signed(-5).unsigned_abs(), sat(5));
+    assert!(SignedAmount::from_sat(-2_100_000_000_000_001).is_err());
+    assert_eq!(SignedAmount::MIN_MONEY.unsigned_abs(), Amount::MAX_MONEY);
+
+    assert_eq!(signed(3).checked_sub(signed(5)), Some(signed(-2)));
+    assert_eq!(SignedAmount::MIN_MONEY.checked_sub(signed(1)), None);
+    assert_eq!(SignedAmount::MAX_MONEY.checked_add(signed(1)), None);
+    assert_eq!(signed(-7).checked_div(2), Some(signed(-3)));
+    assert_eq!(-signed(4) + signed(1), signed(-3));
+    let history = [signed(1500), signed(-250), signed(-1000)];
+    assert_eq!(history.iter().sum::<SignedAmount>(), signed(250));
+
+    assert_eq!(signed(-250).to_string(), "-250 sat");
+    assert_eq!(signed(1500).to_string(), "+1500 sat");
+    assert_eq!(SignedAmount::ZERO.to_string(), "0 sat");
+    assert_eq!(
+        signed(-150_000).to_string_in(Denomination::Bitcoin),
+        "-0.0015 BTC"
+    );
+    assert_eq!(
+        "-0.0015 BTC".parse::<SignedAmount>().unwrap(),
+        signed(-150_000)
+    );
+    assert_eq!("+2,500 sat".parse::<SignedAmount>().unwrap(), signed(2500));
+    assert!("--5".parse::<SignedAmount>().is_err());
+}
+
+#[test]
+fn test_weight_and_fee_rate() {
+    // A 1-input 2-output P2WPKH spend: 113 stripped bytes, 222 in total.
+    let weight = Weight::from_tx_sizes(113, 222);
+    assert_eq!(weight.to_wu(), 561);
+    assert_eq!(weight.to_vbytes_ceil(), 141);
+    assert_eq!(Weight::from_parts(113, 109), weight);
+    assert_eq!(Weight::from_vbytes(141).to_wu(), 564);
+
+    let rate = FeeRate::from_sat_per_vb(10).unwrap();
+    assert_eq!(rate.to_sat_per_kwu(), 2500);
+    assert_eq!(FeeRate::from_sat_per_kvb(10_000), rate);
+    let odd = FeeRate::from_sat_per_kvb(1_001);
+    assert_eq!(odd.to_sat_per_kwu(), 251);
+    assert!(odd.fee_for_vbytes(1_000).unwrap().to_sat() >= 1_001);
+    assert_eq!(rate.fee_for(weight).unwrap().to_sat(), 1403);
+    assert_eq!(rate.fee_for_vbytes(141).unwrap().to_sat(), 1410);
+    assert_eq!(
+        FeeRate::from_sat_per_kwu(1)
+            .fee_for(weight)
+            .unwrap()
+            .to_sat(),
+        1
+    );
+    assert_eq!(FeeRate::ZERO.fee_for(Weight::MAX_BLOCK), Some(Amount::ZERO));
+    assert_eq!(
+        FeeRate::from_sat_per_kwu(u64::MAX).fee_for(Weight::MAX_BLOCK),
+        None
+    );
+
+    let fee = Amount::from_sat(1410).unwrap();
+    assert_eq!(FeeRate::rate_of(fee, Weight::from_vbytes(141)), Some(rate));
+    assert_eq!(FeeRate::rate_of(fee, Weight::ZERO), None);
+    assert_eq!(
+        FeeRate::rate_of(fee, weight).unwrap().to_sat_per_vb_floor(),
+        10
+    );
+    assert_eq!(
+        FeeRate::rate_of(fee, weight).unwrap().to_sat_per_vb_ceil(),
+        11
+    );
+
+    assert_eq!(rate.to_string(), "10 sat/vB");
+    assert_eq!(FeeRate::from_sat_per_kwu(375).to_string(), "1.5 sat/vB");
+    assert_eq!(FeeRate::from_sat_per_kwu(1).to_string(), "0.004 sat/vB");
+    assert_eq!(FeeRate::MIN_RELAY.to_sat_per_vb_floor(), 1);
+}
+
+#[test]
+fn test_input_and_output_sizes() {
+    let vbytes = |input: InputType| input.input_weight().to_vbytes_ceil();
+    assert_eq!(vbytes(InputType::P2PKH), 148);
+    assert_eq!(vbytes(InputType::P2WPKH), 68);
+    assert_eq!(vbytes(InputType::NestedP2WPKH), 91);
+    assert_eq!(InputType::P2TRKeyPath.input_weight().to_wu(), 230);
+    assert_eq!(vbytes(InputType::P2PK), 114);
+
+    let p2sh = InputType::P2SHMultisig {
+        required: 2,
+        total: 3,
+    };
+    assert_eq!(p2sh.script_sig_size(), 254);
+    assert_eq!(p2sh.witness_size(), 0);
+    assert_eq!(vbytes(p2sh), 297);
+    let p2wsh = InputType::P2WSHMultisig {
+        required: 2,
+        total: 3,
+    };
+    assert_eq!(p2wsh.witness_size(), 254);
+    assert_eq!(vbytes(p2wsh), 105);
+    assert_eq!(InputType::P2WPKH.satisfaction_weight().to_wu(), 112);
+
+    let script = hex_to_bytes("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+    assert_eq!(
+        InputType::from_script_pubkey(&decode_script_pubkey(&script)),
+        Some(InputType::P2PKH)
+    );
+    let keys: [&[u8]; 3] = [&[0x02; 33], &[0x03; 33], &[0x02; 33]];
+    let multisig = ScriptBuilder::multisig(1, &keys).unwrap();
+    let bare = InputType::from_script_pubkey(&decode_script_pubkey(&multisig)).unwrap();
+    assert_eq!(
+        bare,
+        InputType::Multisig {
+            required: 1,
+            total: 3
+        }
+    );
+    assert_eq!(bare.script_sig_size(), 74);
+    let p2sh_script = ScriptBuilder::p2sh(&[0; 20]);
+    assert_eq!(
+        InputType::from_script_pubkey(&decode_script_pubkey(&p2sh_script)),
+        None
+    );
+
+    assert_eq!(ScriptType::P2PKH.output_size(), Some(34));
+    assert_eq!(ScriptType::P2WPKH.output_size(), Some(31));
+    assert_eq!(ScriptType::P2SH.output_size(), Some(32));
+    assert_eq!(ScriptType::P2TR.output_size(), Some(43));
+    assert_eq!(ScriptType::NullData.output_size(), None);
+    assert_eq!(
+        output_weight(&multisig).to_wu(),
+        4 * output_size(multisig.len()) as u64
+    );
+    assert_eq!(output_size(300), 311);
+}
+
+#[test]
+fn test_dust_threshold() {
+    let p2pkh = ScriptBuilder::p2pkh(&[0x11; 20]);
+    let p2wpkh = ScriptBuilder::p2wpkh(&[0x11; 20]);
+    let p2tr = ScriptBuilder::p2tr(&[0x11; 32]);
+    let p2sh = ScriptBuilder::p2sh(&[0x11; 20]);
+    let sat = |n| Amount::from_sat(n).unwrap();
+
+    // Core's well-known thresholds at the default 3 sat/vB.
+    assert_eq!(dust_threshold(&p2pkh, FeeRate::DUST_RELAY), sat(546));
+    assert_eq!(dust_threshold(&p2sh, FeeRate::DUST_RELAY), sat(540));
+    assert_eq!(dust_threshold(&p2wpkh, FeeRate::DUST_RELAY), sat(294));
+    assert_eq!(dust_threshold(&p2tr, FeeRate::DUST_RELAY), sat(330));
+    // The script type is ignored: a P2WSH output costs as much to create as
+    // a P2TR one and is priced the same, however large its spend.
+    let p2wsh = ScriptBuilder::p2wsh(&[0x11; 32]);
+    assert_eq!(
+        dust_threshold(&p2wsh, FeeRate::DUST_RELAY),
+        dust_threshold(&p2tr, FeeRate::DUST_RELAY)
+    );
+    assert_eq!(
+        dust_threshold(&ScriptBuilder::null_data(b"hi"), FeeRate::DUST_RELAY),
+        Amount::ZERO
+    );
+    assert_eq!(dust_threshold(&p2pkh, FeeRate::ZERO), Amount::ZERO);
+
+    assert!(is_dust(sat(545), &p2pkh, FeeRate::DUST_RELAY));
+    assert!(!is_dust(sat(546), &p2pkh, FeeRate::DUST_RELAY));
+    assert!(!is_dust(sat(300), &p2wpkh, FeeRate::DUST_RELAY));
+
+    let utxo = UTXO {
+        txid: Txid::from_byte_array([0xaa; 32]),
+        vout: 1,
+        value: sat(293),
+        script_pubkey: p2wpkh,
+        height: 0,
+        is_coinbase: false,
+    };
+    assert!(utxo.is_dust(FeeRate::DUST_RELAY));
+    assert!(!utxo.is_dust(FeeRate::MIN_RELAY));
+}
+
+#[test]
+fn test_hash_types() {
+    // The genesis block's coinbase transaction and header.
+    let tx = hex_to_bytes(concat!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000",
+        "ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368",
+        "616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420",
+        "666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1",
+        "a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112",
+        "de5c384df7ba0b8d578a4c702b6bf11d5fac00000000"
+    ))
+    .unwrap();
+    let txid = Txid::hash(&tx);
+    assert_eq!(
+        txid.to_string(),
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+    );
+    assert_eq!(txid.to_string().parse::<Txid>().unwrap(), txid);
+    assert_eq!(
+        to_big_endian(txid.as_byte_array()),
+        decode_hex(&txid.to_string()).unwrap()
+    );
+
+    let header = hex_to_bytes(concat!(
+        "0100000000000000000000000000000000000000000000000000000000000000000000003b",
+        "a3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff",
+        "001d1dac2b7c"
+    ))
+    .unwrap();
+    let block_hash: BlockHash = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        .parse()
+        .unwrap();
+    assert_eq!(BlockHash::hash(&header), block_hash);
+    assert_eq!(
+        format!("{block_hash:?}"),
+        "BlockHash(000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f)"
+    );
+
+    assert!(matches!(
+        "abcd".parse::<Wtxid>(),
+        Err(Error::InvalidHashLength { length: 2 })
+    ));
+    assert!(matches!(
+        Txid::from_slice(&[0; 33]),
+        Err(Error::InvalidHashLength { length: 33 })
+    ));
+    assert!(matches!(
+        "zz".parse::<Txid>(),
+        Err(Error::InvalidHexCharacter { .. })
+    ));
+    assert!(Txid::all_zeros() < txid);
+
+    let mut seen = std::collections::HashSet::new();
+    assert!(seen.insert(txid));
+    assert!(!seen.insert(txid));
+}
+
+#[test]
+fn test_outpoint_parsing_and_serialization() {
+    let text = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0";
+    let outpoint: Outpoint = text.parse().unwrap();
+    assert_eq!(outpoint.to_string(), text);
+    assert_eq!(outpoint.1, 0);
+
+    let bytes = outpoint.to_bytes();
+    assert_eq!(bytes[0], 0x3b);
+    assert_eq!(&bytes[32..], &[0, 0, 0, 0]);
+    assert_eq!(Outpoint::from_bytes(&bytes).unwrap(), outpoint);
+    assert!(matches!(
+        Outpoint::from_bytes(&bytes[..35]),
+        Err(Error::InvalidOutpointLength { length: 35 })
+    ));
+
+    assert!(Outpoint::NULL.is_null());
+    assert!(!outpoint.is_null());
+    assert_eq!(
+        bytes_to_hex(&Outpoint::NULL.to_bytes()),
+        format!("{}ffffffff", "00".repeat(32))
+    );
+
+    for bad in [
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:",
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:+1",
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:4294967296",
+    ] {
+        assert!(
+            matches!(bad.parse::<Outpoint>(), Err(Error::InvalidOutpoint { .. })),
+            "{bad}"
+        );
+    }
+    assert!(matches!(
+        "abcd:1".parse::<Outpoint>(),
+        Err(Error::InvalidHashLength { length: 2 })
+    ));
+
+    let later = Outpoint(outpoint.0, 1);
+    assert!(outpoint < later);
+    let mut spent = std::collections::BTreeMap::new();
+    spent.insert(later, "b");
+    spent.insert(outpoint, "a");
+    assert_eq!(spent.values().copied().collect::<Vec<_>>(), ["a", "b"]);
+
+    let utxo = UTXO {
+        txid: outpoint.0,
+        vout: 1,
+        value: Amount::ONE_BTC,
+        script_pubkey: ScriptBuilder::p2wpkh(&[0x11; 20]),
+        height: 0,
+        is_coinbase: false,
+    };
+    assert_eq!(utxo.outpoint(), later);
+    assert_eq!(Outpoint::from(&utxo), later);
+    assert_eq!(Outpoint::from(utxo), later);
+}
+
+#[test]
+fn test_utxo_set() {
+    let coin = |vout, sat| UTXO {
+        txid: Txid::from_byte_array([0x11; 32]),
+        vout,
+        value: Amount::from_sat(sat).unwrap(),
+        script_pubkey: ScriptBuilder::p2wpkh(&[0x22; 20]),
+        height: 0,
+        is_coinbase: false,
+    };
+    let mut set = UtxoSet::new();
+    assert!(set.is_empty());
+    set.insert(coin(1, 2_000)).unwrap();
+    set.insert(coin(0, 5_000)).unwrap();
+    assert!(matches!(
+        set.insert(coin(0, 1)),
+        Err(Error::DuplicateUtxo { .. })
+    ));
+    assert_eq!(set.len(), 2);
+    assert_eq!(set.total_value().to_sat(), 7_000);
+    let vouts: Vec<u32> = set.iter().map(|utxo| utxo.vout).collect();
+    assert_eq!(vouts, [0, 1]);
+
+    let outpoint = coin(0, 5_000).outpoint();
+    assert_eq!(set.get(&outpoint), Some(&coin(0, 5_000)));
+    assert_eq!(set.spend(&outpoint).unwrap(), coin(0, 5_000));
+    assert!(!set.contains(&outpoint));
+    // A spent coin is forgotten, so spending it again finds nothing.
+    assert!(matches!(
+        set.spend(&outpoint),
+        Err(Error::MissingUtxo { outpoint: o }) if o == outpoint
+    ));
+    let missing = Outpoint(Txid::all_zeros(), 7);
+    assert!(matches!(
+        set.spend(&missing),
+        Err(Error::MissingUtxo { outpoint: o }) if o == missing
+    ));
+    assert_eq!(
+        set.spend(&missing).unwrap_err().to_string(),
+        format!("no unspent output at {}:7", Txid::all_zeros())
+    );
+    assert_eq!((&set).into_iter().count(), 1);
+    assert_eq!(set.total_value().to_sat(), 2_000);
+
+    set.insert(coin(0, 5_000)).unwrap();
+    assert!(set.contains(&outpoint));
+}
+
+fn coinbase_tx(height: u32, outputs: Vec<TxOut>) -> Transaction {
+    Transaction {
+        version: 2,
+        inputs: vec![TxIn {
+            previous_output: Outpoint::NULL,
+            script_sig: ScriptBuilder::new().push_int(height as i64).into_script(),
+            sequence: u32::MAX,
+            witness: vec![vec![0; 32]],
+        }],
+        outputs,
+        lock_time: 0,
+    }
+}
+
+fn spend_tx(inputs: &[Outpoint], outputs: Vec<TxOut>) -> Transaction {
+    Transaction {
+        version: 2,
+        inputs: inputs
+            .iter()
+            .map(|&previous_output| TxIn {
+                previous_output,
+                script_sig: Vec::new(),
+                sequence: u32::MAX,
+                witness: vec![vec![0x30; 72], vec![0x02; 33]],
+            })
+            .collect(),
+        outputs,
+        lock_time: 0,
+    }
+}
+
+fn tx_out(sat: u64, key_hash: u8) -> TxOut {
+    TxOut {
+        value: Amount::from_sat(sat).unwrap(),
+        script_pubkey: ScriptBuilder::p2wpkh(&[key_hash; 20]),
+    }
+}
+
+#[test]
+fn test_transaction_serialization() {
+    // The genesis coinbase, whose txid is well known.
+    let genesis = Transaction {
+        version: 1,
+        inputs: vec![TxIn {
+            previous_output: Outpoint::NULL,
+            script_sig: hex_to_bytes(concat!(
+                "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63",
+                "656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f",
+                "722062616e6b73"
+            ))
+            .unwrap(),
+            sequence: u32::MAX,
+            witness: Vec::new(),
+        }],
+        outputs: vec![TxOut {
+            value: Amount::from_sat(50 * 100_000_000).unwrap(),
+            script_pubkey: hex_to_bytes(concat!(
+                "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649",
+                "f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac"
+            ))
+            .unwrap(),
+        }],
+        lock_time: 0,
+    };
+    assert!(genesis.is_coinbase());
+    assert_eq!(
+        genesis.txid().to_string(),
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+    );
+    assert_eq!(genesis.serialize().len(), 204);
+    assert_eq!(genesis.weight().to_wu(), 816);
+    assert_eq!(
+        genesis.wtxid().to_byte_array(),
+        genesis.txid().to_byte_array()
+    );
+
+    let spend = spend_tx(&[Outpoint(genesis.txid(), 0)], vec![tx_out(1_000, 1)]);
+    assert!(!spend.is_coinbase());
+    let stripped = spend.serialize_without_witness().len() as u64;
+    let total = spend.serialize().len() as u64;
+    assert_eq!(stripped, 82);
+    assert_eq!(total, 82 + 2 + 1 + 1 + 72 + 1 + 33);
+    assert_eq!(spend.weight(), Weight::from_tx_sizes(stripped, total));
+    assert_ne!(spend.wtxid().to_byte_array(), spend.txid().to_byte_array());
+}
+
+#[test]
+fn test_apply_and_undo_blocks() {
+    let mut set = UtxoSet::new();
+    let coinbase1 = coinbase_tx(1, vec![tx_out(5_000, 1), tx_out(3_000, 2)]);
+    let undo1 = set
+        .apply_block(1, std::slice::from_ref(&coinbase1))
+        .unwrap();
+    assert!(undo1.spent.is_empty());
+    assert_eq!(set.len(), 2);
+    let first = set.get(&Outpoint(coinbase1.txid(), 0)).unwrap();
+    assert!(first.is_coinbase);
+    assert_eq!(first.height, 1);
+
+    // Block 2 spends the first coin, then spends the resulting output in
+    // the same block; the OP_RETURN output never enters the set.
+    let coinbase2 = coinbase_tx(2, vec![tx_out(100, 3)]);
+    let spend_a = spend_tx(
+        &[Outpoint(coinbase1.txid(), 0)],
+        vec![
+            tx_out(4_000, 4),
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuilder::null_data(b"memo"),
+            },
+        ],
+    );
+    let spend_b = spend_tx(&[Outpoint(spend_a.txid(), 0)], vec![tx_out(3_500, 5)]);
+    let block2 = [coinbase2.clone(), spend_a.clone(), spend_b.clone()];
+    let before = set.clone();
+    let undo2 = set.apply_block(2, &block2).unwrap();
+
+    assert_eq!(undo2.spent.len(), 2);
+    assert_eq!(undo2.spent[0][0].value.to_sat(), 5_000);
+    assert_eq!(undo2.spent[0][0].height, 1);
+    assert!(undo2.spent[0][0].is_coinbase);
+    assert_eq!(undo2.spent[1][0].height, 2);
+    assert!(!set.contains(&Outpoint(spend_a.txid(), 1)));
+    let vouts: Vec<(Txid, u64)> = set.iter().map(|u| (u.txid, u.value.to_sat())).collect();
+    assert_eq!(vouts.len(), 3);
+    assert_eq!(set.total_value().to_sat(), 3_000 + 100 + 3_500);
+
+    set.undo_block(&block2, &undo2).unwrap();
+    assert_eq!(
+        set.iter().collect::<Vec<_>>(),
+        before.iter().collect::<Vec<_>>()
+    );
+    assert!(set.contains(&Outpoint(coinbase1.txid(), 0)));
+
+    // A block whose last transaction double-spends leaves no trace.
+    let conflict = spend_tx(&[Outpoint(coinbase1.txid(), 0)], vec![tx_out(1, 6)]);
+    let bad = [coinbase2.clone(), spend_a.clone(), conflict];
+    assert!(matches!(
+        set.apply_block(2, &bad),
+        Err(Error::MissingUtxo { .. })
+    ));
+    assert_eq!(
+        set.iter().collect::<Vec<_>>(),
+        before.iter().collect::<Vec<_>>()
+    );
+
+    let missing = spend_tx(&[Outpoint(Txid::all_zeros(), 9)], vec![]);
+    assert!(matches!(
+        set.apply_block(2, &[missing]),
+        Err(Error::MissingUtxo { .. })
+    ));
+
+    // Undo data that does not belong to the block is refused atomically.
+    let undo2 = set.apply_block(2, &block2).unwrap();
+    let after = set.clone();
+    assert!(matches!(
+        set.undo_block(&block2, &BlockUndo::default()),
+        Err(Error::UndoMismatch)
+    ));
+    let mut swapped = undo2.clone();
+    swapped.spent.swap(0, 1);
+    assert!(matches!(
+        set.undo_block(&block2, &swapped),
+        Err(Error::UndoMismatch)
+    ));
+    assert_eq!(
+        set.iter().collect::<Vec<_>>(),
+        after.iter().collect::<Vec<_>>()
+    );
+    set.undo_block(&block2, &undo2).unwrap();
+    set.undo_block(&[coinbase1], &undo1).unwrap();
+    assert!(set.is_empty());
+}
+
+/// A fresh directory for one test's store files.
+fn store_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("utxo-store-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_file_utxo_store_persists_and_recovers() {
+    let dir = store_dir("recover");
+    let coinbase1 = coinbase_tx(1, vec![tx_out(5_000, 1), tx_out(3_000, 2)]);
+    let spend = spend_tx(&[Outpoint(coinbase1.txid(), 0)], vec![tx_out(4_000, 3)]);
+    let block2 = [coinbase_tx(2, vec![tx_out(100, 4)]), spend.clone()];
+
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), None);
+    store
+        .apply_block(1, std::slice::from_ref(&coinbase1))
+        .unwrap();
+    let undo2 = store.apply_block(2, &block2).unwrap();
+    let utxo = UTXO {
+        txid: Txid::all_zeros(),
+        vout: 7,
+        value: Amount::from_sat(42).unwrap(),
+        script_pubkey: vec![0x51],
+        height: 0,
+        is_coinbase: false,
+    };
+    UtxoStore::insert(&mut store, utxo.clone()).unwrap();
+    let expected = store.utxos().clone();
+    drop(store);
+
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), Some(2));
+    assert_eq!(
+        store.utxos().iter().collect::<Vec<_>>(),
+        expected.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(UtxoStore::get(&store, &utxo.outpoint()), Some(&utxo));
+    assert!(matches!(
+        UtxoStore::spend(&mut store, &Outpoint(coinbase1.txid(), 0)),
+        Err(Error::MissingUtxo { .. })
+    ));
+    drop(store);
+
+    // A crash halfway through writing a batch loses only that batch.
+    let log = dir.join("log");
+    let committed = std::fs::read(&log).unwrap();
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    UtxoStore::spend(&mut store, &utxo.outpoint()).unwrap();
+    drop(store);
+    let mut torn = std::fs::read(&log).unwrap();
+    torn.truncate(committed.len() + (torn.len() - committed.len()) / 2);
+    std::fs::write(&log, &torn).unwrap();
+
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(UtxoStore::get(&store, &utxo.outpoint()), Some(&utxo));
+    assert_eq!(std::fs::read(&log).unwrap(), committed);
+
+    // Trailing garbage is dropped the same way, and writes resume after it.
+    drop(store);
+    let mut garbage = committed.clone();
+    garbage.extend([0xff; 13]);
+    std::fs::write(&log, &garbage).unwrap();
+    store = FileUtxoStore::open(&dir).unwrap();
+    store.undo_block(&block2, &undo2).unwrap();
+    assert_eq!(store.tip_height(), Some(1));
+    drop(store);
+
+    let store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), Some(1));
+    assert!(store.utxos().contains(&Outpoint(coinbase1.txid(), 0)));
+    assert!(!store.utxos().contains(&Outpoint(spend.txid(), 0)));
+    assert_eq!(store.utxos().total_value().to_sat(), 5_000 + 3_000 + 42);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_utxo_store_compaction() {
+    let dir = store_dir("compact");
+    let mut store = FileUtxoStore::open(&dir).unwrap().with_snapshot_interval(2);
+    let mut undos = Vec::new();
+    let mut blocks = Vec::new();
+    for height in 1..=5 {
+        let block = vec![coinbase_tx(height, vec![tx_out(1_000, height as u8)])];
+        undos.push(store.apply_block(height, &block).unwrap());
+        blocks.push(block);
+    }
+    // The fourth block triggered a snapshot, leaving only the fifth logged.
+    assert!(dir.join("snapshot").exists());
+    let spend = spend_tx(&[Outpoint(blocks[0][0].txid(), 0)], vec![tx_out(900, 9)]);
+    let block6 = vec![coinbase_tx(6, vec![]), spend];
+    undos.push(store.apply_block(6, &block6).unwrap());
+    blocks.push(block6);
+    store.compact().unwrap();
+    let expected = store.utxos().clone();
+    drop(store);
+
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), Some(6));
+    assert_eq!(
+        store.utxos().iter().collect::<Vec<_>>(),
+        expected.iter().collect::<Vec<_>>()
+    );
+    assert!(!store.utxos().contains(&Outpoint(blocks[0][0].txid(), 0)));
+
+    for (block, undo) in blocks.iter().zip(&undos).rev() {
+        store.undo_block(block, undo).unwrap();
+    }
+    assert_eq!(store.tip_height(), Some(0));
+    assert!(store.utxos().is_empty());
+    drop(store);
+
+    // A damaged snapshot is reported rather than silently ignored.
+    let snapshot = dir.join("snapshot");
+    let mut bytes = std::fs::read(&snapshot).unwrap();
+    bytes[20] ^= 1;
+    std::fs::write(&snapshot, &bytes).unwrap();
+    assert!(matches!(
+        FileUtxoStore::open(&dir),
+        Err(Error::CorruptStore)
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_utxo_store_compaction_failure() {
+    let dir = store_dir("compact-fail");
+    let mut store = FileUtxoStore::open(&dir).unwrap().with_snapshot_interval(1);
+    // A directory where the snapshot is staged makes every compaction fail.
+    std::fs::create_dir_all(dir.join("snapshot.tmp")).unwrap();
+    let block1 = [coinbase_tx(1, vec![tx_out(1_000, 1)])];
+    let block2 = [coinbase_tx(2, vec![tx_out(2_000, 2)])];
+    store.apply_block(1, &block1).unwrap();
+    // The batch was durable, so the failed compaction is kept aside rather
+    // than returned, and the store keeps working.
+    assert!(matches!(store.take_compaction_error(), Some(Error::Io(_))));
+    assert!(store.take_compaction_error().is_none());
+    assert!(store.compact().is_err());
+    store.apply_block(2, &block2).unwrap();
+    assert!(store.take_compaction_error().is_some());
+    assert!(!dir.join("snapshot").exists());
+    drop(store);
+
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), Some(2));
+    assert_eq!(store.utxos().total_value().to_sat(), 3_000);
+
+    // Once compaction can run again it folds the log into a snapshot.
+    std::fs::remove_dir(dir.join("snapshot.tmp")).unwrap();
+    let coin = UTXO {
+        txid: Txid::all_zeros(),
+        vout: 0,
+        value: Amount::from_sat(5).unwrap(),
+        script_pubkey: vec![0x51],
+        height: 2,
+        is_coinbase: false,
+    };
+    UtxoStore::insert(&mut store, coin).unwrap();
+    assert!(store.take_compaction_error().is_none());
+    store.compact().unwrap();
+    assert!(dir.join("snapshot").exists());
+    drop(store);
+    let store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.tip_height(), Some(2));
+    assert_eq!(store.utxos().total_value().to_sat(), 3_005);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_utxo_store_unreadable_log() {
+    let dir = store_dir("unreadable-log");
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    store
+        .apply_block(1, &[coinbase_tx(1, vec![tx_out(1_000, 1)])])
+        .unwrap();
+    store.compact().unwrap();
+    drop(store);
+
+    // A log that exists but cannot be read is an error, not a missing log
+    // to start afresh.
+    let snapshot = std::fs::read(dir.join("snapshot")).unwrap();
+    std::fs::remove_file(dir.join("log")).unwrap();
+    std::fs::create_dir(dir.join("log")).unwrap();
+    assert!(matches!(FileUtxoStore::open(&dir), Err(Error::Io(_))));
+    assert!(dir.join("log").is_dir());
+    assert_eq!(std::fs::read(dir.join("snapshot")).unwrap(), snapshot);
+    // Likewise a log that cannot even be opened, here a symlink loop.
+    #[cfg(unix)]
+    {
+        std::fs::remove_dir(dir.join("log")).unwrap();
+        std::os::unix::fs::symlink("log", dir.join("log")).unwrap();
+        assert!(matches!(FileUtxoStore::open(&dir), Err(Error::Io(_))));
+        assert!(dir.join("log").is_symlink());
+        assert_eq!(std::fs::read(dir.join("snapshot")).unwrap(), snapshot);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_file_utxo_store_failed_append() {
+    // Writes past the file size limit fail, so the test reruns itself in a
+    // shell that lowers the limit to one 512-byte block.
+    if std::env::var_os("UTXO_STORE_SIZE_LIMITED").is_none() {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("ulimit -f 1 && trap '' XFSZ && exec \"$0\" \"$@\"")
+            .arg(std::env::current_exe().unwrap())
+            .args(["--exact", "test_file_utxo_store_failed_append"])
+            .env("UTXO_STORE_SIZE_LIMITED", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("1 passed"), "{stdout}");
+        return;
+    }
+
+    let dir = store_dir("failed-append");
+    let log = dir.join("log");
+    let coin = |vout: u32| UTXO {
+        txid: Txid::all_zeros(),
+        vout,
+        value: Amount::from_sat(1_000).unwrap(),
+        script_pubkey: vec![0x51],
+        height: 1,
+        is_coinbase: false,
+    };
+    let mut store = FileUtxoStore::open(&dir).unwrap();
+    let mut vout = 0;
+    let (error, muhash, committed) = loop {
+        let (muhash, committed) = (store.utxos().muhash(), std::fs::read(&log).unwrap());
+        match UtxoStore::insert(&mut store, coin(vout)) {
+            Ok(()) => vout += 1,
+            Err(e) => break (e, muhash, committed),
+        }
+        assert!(vout < 100, "the log never filled up");
+    };
+
+    // The coin that did not fit is gone from memory, and the part of its
+    // batch that reached the log was cut off again.
+    assert!(matches!(error, Error::Io(_)));
+    assert_eq!(UtxoStore::get(&store, &coin(vout).outpoint()), None);
+    assert_eq!(store.utxos().len(), vout as usize);
+    assert_eq!(store.utxos().muhash(), muhash);
+    assert_eq!(std::fs::read(&log).unwrap(), committed);
+    drop(store);
+
+    let store = FileUtxoStore::open(&dir).unwrap();
+    assert_eq!(store.utxos().len(), vout as usize);
+    assert_eq!(store.utxos().muhash(), muhash);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_coin_compression() {
+    // Core's compress_tests.
+    const COIN: u64 = 100_000_000;
+    for (sat, compressed) in [
+        (0, 0x0),
+        (1, 0x1),
+        (1_000_000, 0x7),
+        (COIN, 0x9),
+        (50 * COIN, 0x32),
+        (21_000_000 * COIN, 0x1406f40),
+    ] {
+        assert_eq!(compress_amount(sat), compressed);
+        assert_eq!(decompress_amount(compressed), sat);
+    }
+    for sat in (0..100_000).chain((0..100_000).map(|n| n * 1_000_000)) {
+        assert_eq!(decompress_amount(compress_amount(sat)), sat);
+    }
+    for x in 0..100_000 {
+        assert_eq!(compress_amount(decompress_amount(x)), x);
+    }
+    // Far past the supply, the arithmetic wraps as Core's uint64_t does.
+    assert_eq!(compress_amount(u64::MAX), 0xffff_ffff_ffff_fff3);
+
+    // Core's coins_tests: stored coins and the scripts they expand to.
+    let outpoint = Outpoint(Txid::all_zeros(), 0);
+    let coin = decompress_coin(
+        outpoint,
+        &decode_hex("97f23c835800816115944e077fe7c803cfa57f29b36bf87c1d35").unwrap(),
+    )
+    .unwrap();
+    assert!(!coin.is_coinbase);
+    assert_eq!(coin.height, 203_998);
+    assert_eq!(coin.value.to_sat(), 60_000_000_000);
+    let hash: [u8; 20] = decode_hex("816115944e077fe7c803cfa57f29b36bf87c1d35")
+        .unwrap()
+        .try_into()
+        .unwrap();
+    assert_eq!(coin.script_pubkey, ScriptBuilder::p2pkh(&hash));
+
+    let bytes = decode_hex("8ddf77bbd123008c988f1a4a4de2161e0f50aac7f17e7f9555caa4").unwrap();
+    let coin = decompress_coin(outpoint, &bytes).unwrap();
+    assert!(coin.is_coinbase);
+    assert_eq!(coin.height, 120_891);
+    assert_eq!(coin.value.to_sat(), 110_397);
+    assert_eq!(compress_coin(&coin), bytes);
+
+    let coin = decompress_coin(outpoint, &[0x00, 0x00, 0x06]).unwrap();
+    assert_eq!((coin.height, coin.value.to_sat()), (0, 0));
+    assert!(coin.script_pubkey.is_empty());
+    // Scripts that run past the end of the data, and 22 million BTC.
+    for hex in ["000007", "00008a95c0bb00", "0089bde20006"] {
+        assert!(matches!(
+            decompress_coin(outpoint, &decode_hex(hex).unwrap()),
+            Err(Error::InvalidCoin)
+        ));
+    }
+
+    // Core's VARINT bit patterns, through the height code.
+    for (height, is_coinbase, varint) in [
+        (0, false, "00"),
+        (0x40, false, "8000"),
+        (0x91a, false, "a334"),
+        (0x7fff, true, "82fe7f"),
+        (0x91a2b, false, "c7e756"),
+        (0x7fff_ffff, true, "8efefefe7f"),
+    ] {
+        let utxo = UTXO {
+            txid: Txid::all_zeros(),
+            vout: 0,
+            value: Amount::ZERO,
+            script_pubkey: vec![],
+            height,
+            is_coinbase,
+        };
+        let bytes = compress_coin(&utxo);
+        assert_eq!(bytes_to_hex(&bytes), format!("{varint}0006"));
+        assert_eq!(decompress_coin(outpoint, &bytes).unwrap(), utxo);
+    }
+
+    // The script templates, including P2PK with the generator point.
+    let g_x = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    let g_y = "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+    let uncompressed = decode_hex(&format!("04{g_x}{g_y}")).unwrap();
+    let compressed_key = decode_hex(&format!("02{g_x}")).unwrap();
+    let p2pk = |key: &[u8]| {
+        ScriptBuilder::new()
+            .push_slice(key)
+            .push_opcode(Opcode::OpChecksig)
+            .into_script()
+    };
+    for (script, code) in [
+        (ScriptBuilder::p2pkh(&hash), 0x00),
+        (ScriptBuilder::p2sh(&hash), 0x01),
+        (p2pk(&compressed_key), 0x02),
+        (p2pk(&uncompressed), 0x04),
+    ] {
+        let compressed = compress_script(&script).unwrap();
+        assert_eq!(compressed[0], code);
+        assert_eq!(decompress_script(code, &compressed[1..]).unwrap(), script);
+    }
+    assert_eq!(
+        &compress_script(&p2pk(&uncompressed)).unwrap()[1..],
+        &decode_hex(g_x).unwrap()[..]
+    );
+    // A key off the curve, and other templates, stay uncompressed.
+    let mut off_curve = uncompressed.clone();
+    off_curve[64] ^= 1;
+    assert_eq!(compress_script(&p2pk(&off_curve)), None);
+    assert_eq!(compress_script(&ScriptBuilder::p2wpkh(&hash)), None);
+    assert_eq!(decompress_script(0x02, &[0; 20]), None);
+    assert_eq!(decompress_script(0x06, &[0; 32]), None);
+
+    let utxo = UTXO {
+        txid: Txid::all_zeros(),
+        vout: 3,
+        value: Amount::from_sat(1_234).unwrap(),
+        script_pubkey: ScriptBuilder::p2wpkh(&hash),
+        height: 800_000,
+        is_coinbase: false,
+    };
+    let bytes = compress_coin(&utxo);
+    assert_eq!(bytes.len(), 3 + 2 + 1 + 22);
+    assert_eq!(decompress_coin(utxo.outpoint(), &bytes).unwrap(), utxo);
+    assert!(matches!(
+        decompress_coin(utxo.outpoint(), &[&bytes[..], &[0]].concat()),
+        Err(Error::InvalidCoin)
+    ));
+}
+
+#[test]
+fn test_utxo_set_hashes() {
+    // Core's muhash_tests: {0} * {1} / {2}.
+    let element = |n: u8| {
+        let mut data = [0u8; 32];
+        data[0] = n;
+        data
+    };
+    let mut muhash = MuHash3072::new();
+    muhash.insert(&element(0));
+    muhash.insert(&element(1));
+    muhash.remove(&element(2));
+    assert_eq!(
+        muhash.finalize().to_string(),
+        "10d312b100cbd32ada024a6646e40d3482fcff103668d2625f10002a607d5863"
+    );
+    // Order does not matter, and removing an element undoes inserting it.
+    let mut reordered = MuHash3072::new();
+    reordered.remove(&element(2));
+    reordered.insert(&element(1));
+    reordered.insert(&element(3));
+    reordered.insert(&element(0));
+    reordered.remove(&element(3));
+    assert_eq!(reordered.finalize(), muhash.finalize());
+
+    // The empty set, as gettxoutsetinfo reports it on a fresh regtest chain.
+    let mut set = UtxoSet::new();
+    let empty = "dd5ad2a105c2d29495f577245c357409002329b9f4d6182c0af3dc2f462555c8";
+    assert_eq!(set.muhash().to_string(), empty);
+
+    let coinbase1 = coinbase_tx(1, vec![tx_out(5_000, 1), tx_out(3_000, 2)]);
+    set.apply_block(1, std::slice::from_ref(&coinbase1))
+        .unwrap();
+    let after_block1 = set.muhash();
+    let spend = spend_tx(&[Outpoint(coinbase1.txid(), 0)], vec![tx_out(4_000, 3)]);
+    let block2 = [coinbase_tx(2, vec![tx_out(100, 4)]), spend];
+    let undo2 = set.apply_block(2, &block2).unwrap();
+
+    // The rolling hash agrees with one built from the coins directly.
+    let mut rebuilt = UtxoSet::new();
+    for utxo in set.iter().collect::<Vec<_>>().into_iter().rev() {
+        rebuilt.insert(utxo.clone()).unwrap();
+    }
+    assert_eq!(rebuilt.muhash(), set.muhash());
+    assert_ne!(set.muhash(), after_block1);
+
+    // A block that fails part way leaves the hash untouched too.
+    let before = set.muhash();
+    let conflict = spend_tx(&[Outpoint(coinbase1.txid(), 0)], vec![]);
+    assert!(
+        set.apply_block(3, &[coinbase_tx(3, vec![]), conflict])
+            .is_err()
+    );
+    assert_eq!(set.muhash(), before);
+
+    set.undo_block(&block2, &undo2).unwrap();
+    assert_eq!(set.muhash(), after_block1);
+    let outpoint = Outpoint(coinbase1.txid(), 1);
+    let utxo = set.spend(&outpoint).unwrap();
+    set.spend(&Outpoint(coinbase1.txid(), 0)).unwrap();
+    assert_eq!(set.muhash().to_string(), empty);
+    set.insert(utxo).unwrap();
+
+    let best = BlockHash::hash(b"best");
+    assert_ne!(
+        set.hash_serialized(&best),
+        set.hash_serialized(&BlockHash::all_zeros())
+    );
+
+    // A fixed set hashed by hand from TxOutSer: the outpoint, height * 2 +
+    // is_coinbase as a u32, the value as an i64 and the script with its
+    // compact size, all after the regtest genesis hash.
+    let mut known = UtxoSet::new();
+    known
+        .insert(UTXO {
+            txid: Txid::from_byte_array([0x11; 32]),
+            vout: 0,
+            value: Amount::from_sat(5_000_000_000).unwrap(),
+            script_pubkey: ScriptBuilder::p2pkh(&[0x22; 20]),
+            height: 1,
+            is_coinbase: true,
+        })
+        .unwrap();
+    known
+        .insert(UTXO {
+            txid: Txid::from_byte_array([0x33; 32]),
+            vout: 1,
+            value: Amount::from_sat(1_234).unwrap(),
+            script_pubkey: ScriptBuilder::p2wpkh(&[0x44; 20]),
+            height: 2,
+            is_coinbase: false,
+        })
+        .unwrap();
+    let genesis: BlockHash = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        known.hash_serialized(&genesis).to_string(),
+        "7b999e3091ed3f2d438607a0afb0cf3bd1d27ceabcd6d2d7382bf4e3abcd7070"
+    );
+    assert_eq!(
+        known.muhash().to_string(),
+        "684cb35c1d4e2b24833f0176b206336bc08f823928c707fec869ed9e24f618cc"
+    );
+}
+
+#[test]
+fn test_coinbase_maturity_and_balances() {
+    let coin = |height: u32, is_coinbase: bool, sat: u64, vout: u32| UTXO {
+        txid: Txid::all_zeros(),
+        vout,
+        value: Amount::from_sat(sat).unwrap(),
+        script_pubkey: vec![],
+        height,
+        is_coinbase,
+    };
+
+    let coinbase = coin(100, true, 5_000, 0);
+    assert_eq!(coinbase.confirmations(99), 0);
+    assert_eq!(coinbase.confirmations(100), 1);
+    assert_eq!(coinbase.confirmations(198), 99);
+    // Spendable in block 200, a hundred blocks after its own.
+    assert!(!coinbase.is_mature(198));
+    assert!(coinbase.is_mature(199));
+    assert!(!coinbase.is_mature(50));
+
+    let regular = coin(100, false, 2_000, 1);
+    assert!(regular.is_mature(100));
+    assert!(regular.is_mature(0));
+    let pending = coin(UTXO::MEMPOOL_HEIGHT, false, 700, 2);
+    assert_eq!(pending.confirmations(u32::MAX), 0);
+    assert_eq!(pending.confirmations(800_000), 0);
+    // The deepest possible coin saturates rather than overflowing.
+    assert_eq!(coin(0, false, 1, 4).confirmations(u32::MAX), u32::MAX);
+
+    let wallet = TestWallet {
+        utxos: vec![coinbase, regular, pending, coin(10, true, 300, 3)],
+        scripts: vec![vec![]],
+        history: vec![],
+    };
+    let balances = wallet.balance_breakdown(150);
+    assert_eq!(balances.confirmed.to_sat(), 2_000 + 300);
+    assert_eq!(balances.untrusted_pending.to_sat(), 700);
+    assert_eq!(balances.immature.to_sat(), 5_000);
+    assert_eq!(balances.total(), wallet.balance());
+    assert_eq!(wallet.balance_breakdown(199).confirmed.to_sat(), 7_300);
+    // Below the coins' heights, nothing is confirmed yet.
+    assert_eq!(
+        wallet.balance_breakdown(5).untrusted_pending,
+        wallet.balance()
+    );
+}
+
+#[test]
+fn test_wallet_trait_defaults() {
+    let mine = ScriptBuilder::p2wpkh(&[1; 20]);
+    let coin = |txid: Txid, height: u32, is_coinbase: bool, sat: u64| UTXO {
+        txid,
+        vout: 0,
+        value: Amount::from_sat(sat).unwrap(),
+        script_pubkey: mine.clone(),
+        height,
+        is_coinbase,
+    };
+    let (received, change, incoming, mined) = (
+        Txid::hash(b"received"),
+        Txid::hash(b"change"),
+        Txid::hash(b"incoming"),
+        Txid::hash(b"mined"),
+    );
+    let wallet = TestWallet {
+        utxos: vec![
+            coin(received, 10, false, 40_000),
+            coin(change, UTXO::MEMPOOL_HEIGHT, false, 9_000),
+            coin(incoming, UTXO::MEMPOOL_HEIGHT, false, 2_500),
+            coin(mined, 50, true, 625_000),
+        ],
+        scripts: vec![mine.clone()],
+        history: vec![
+            WalletTx {
+                txid: received,
+                height: 10,
+                amount: SignedAmount::from_sat(40_000).unwrap(),
+                fee: None,
+                from_me: false,
+            },
+            WalletTx {
+                txid: change,
+                height: UTXO::MEMPOOL_HEIGHT,
+                amount: SignedAmount::from_sat(-1_200).unwrap(),
+                fee: Some(Amount::from_sat(200).unwrap()),
+                from_me: true,
+            },
+        ],
+    };
+
+    assert!(wallet.is_mine(&mine));
+    assert!(!wallet.is_mine(&ScriptBuilder::p2wpkh(&[2; 20])));
+    assert_eq!(wallet.list_unspent().len(), 4);
+    assert_eq!(wallet.balance().to_sat(), 676_500);
+
+    let balances = wallet.balance_breakdown(100);
+    assert_eq!(
+        balances,
+        Balances {
+            confirmed: Amount::from_sat(40_000).unwrap(),
+            trusted_pending: Amount::from_sat(9_000).unwrap(),
+            untrusted_pending: Amount::from_sat(2_500).unwrap(),
+            immature: Amount::from_sat(625_000).unwrap(),
+        }
+    );
+    assert_eq!(balances.total(), wallet.balance());
+    assert_eq!(wallet.balance_breakdown(149).confirmed.to_sat(), 665_000);
+
+    // Only confirmed coins are spendable, and coinbase outputs once mature.
+    let txids = |utxos: Vec<UTXO>| utxos.iter().map(|utxo| utxo.txid).collect::<Vec<_>>();
+    assert_eq!(txids(wallet.spendable(100)), [received]);
+    assert_eq!(txids(wallet.spendable(148)), [received]);
+    assert_eq!(txids(wallet.spendable(149)), [received, mined]);
+    assert!(wallet.spendable(9).is_empty());
+
+    // Coins paying to scripts the wallet does not own are left out.
+    let mut watched = wallet.clone();
+    watched.utxos.push(UTXO {
+        script_pubkey: ScriptBuilder::p2wpkh(&[2; 20]),
+        ..coin(Txid::hash(b"other"), 10, false, 70_000)
+    });
+    assert_eq!(watched.list_unspent().len(), 5);
+    assert_eq!(watched.balance(), wallet.balance());
+    assert_eq!(
+        watched.balance_breakdown(100),
+        wallet.balance_breakdown(100)
+    );
+    assert_eq!(watched.spendable(100), wallet.spendable(100));
+}